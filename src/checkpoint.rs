@@ -0,0 +1,334 @@
+use std::{
+    fmt, fs, io,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::{to_raw_value, RawValue};
+
+use crate::{ConfigError, GaConfig, Geometry, Key, Keyboard, LayoutError, MoveConfig};
+
+/// Everything needed to carry on an interrupted [`go`](crate::go) run:
+/// the config it was started with and the state of every group.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    /// The run's config, with the seed filled in.
+    pub config: GaConfig,
+    /// Geometry of every keyboard in the run.
+    pub geometry: Arc<Geometry>,
+    pub start: Option<Keyboard>,
+    pub groups: Vec<GroupState>,
+}
+
+/// One group's progress, saved at the end of a generation.
+#[derive(Debug, Clone)]
+pub struct GroupState {
+    pub generation: usize,
+    /// Keyboards to be scored in the next generation. Empty until the group
+    /// has been started.
+    pub population: Vec<Keyboard>,
+    pub top: Vec<(f32, Keyboard)>,
+    pub score_history: Vec<f32>,
+    pub rng: RngState,
+    /// The group's best keyboard once it has stopped improving.
+    pub result: Option<(f32, Keyboard)>,
+}
+
+/// Position of a ChaCha stream, enough to rebuild the generator exactly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RngState {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    pub word_pos: u128,
+}
+
+impl From<&ChaCha8Rng> for RngState {
+    fn from(rng: &ChaCha8Rng) -> Self {
+        Self { seed: rng.get_seed(), stream: rng.get_stream(), word_pos: rng.get_word_pos() }
+    }
+}
+
+impl From<&RngState> for ChaCha8Rng {
+    fn from(state: &RngState) -> Self {
+        let mut rng = ChaCha8Rng::from_seed(state.seed);
+        rng.set_stream(state.stream);
+        rng.set_word_pos(state.word_pos);
+        rng
+    }
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// The file reads but can't be the state of a run.
+    Invalid(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "could not access checkpoint: {}", e),
+            CheckpointError::Format(e) => write!(f, "bad checkpoint: {}", e),
+            CheckpointError::Invalid(msg) => write!(f, "invalid checkpoint: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> Self { CheckpointError::Io(e) }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(e: serde_json::Error) -> Self { CheckpointError::Format(e) }
+}
+
+impl Checkpoint {
+    /// A run that hasn't started yet on `geometry`, starting from `start` if
+    /// given. Picks a seed if the config has none, and applies its `[moves]`
+    /// to the geometry and the start, which has to meet its constraints.
+    pub fn new(config: &GaConfig, geometry: Arc<Geometry>, start: Option<&Keyboard>) -> Result<Self, ConfigError> {
+        let mut config = config.clone();
+        let seed = *config.seed.get_or_insert_with(rand::random);
+
+        let groups = (0..config.groups)
+            .map(|group| {
+                // every group draws from its own stream of the seed so the result
+                // doesn't depend on how rayon schedules the groups
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                rng.set_stream(group as u64);
+                GroupState {
+                    generation: 0,
+                    population: vec![],
+                    top: vec![],
+                    score_history: vec![],
+                    rng: RngState::from(&rng),
+                    result: None,
+                }
+            })
+            .collect();
+
+        let keys = start.map_or(Keyboard::new_47().keys.len(), |start| start.keys.len());
+        if keys != geometry.len() {
+            return Err(ConfigError::Invalid(match start {
+                Some(_) => format!("the start layout has {} keys but the geometry has {}", keys, geometry.len()),
+                None => format!("without a start layout the geometry needs {} keys, not {}", keys, geometry.len()),
+            }))
+        }
+
+        // the geometry's movable positions are those of new_47's letters, so a
+        // start layout moves its own letters unless the config says otherwise
+        let moves = match (start, &config.moves.movable) {
+            (Some(start), None) => {
+                let start = config.moves.pin(&start.on(geometry.clone()))?;
+                let letters = (0..start.keys.len()).filter(|&i| matches!(start.keys[i], Key::Letter(_, _)));
+                MoveConfig { movable: Some(letters.collect()), ..config.moves.clone() }
+            },
+            _ => config.moves.clone(),
+        };
+        let geometry = moves.apply(&geometry)?;
+        // without a start the run begins from random layouts of the built-in one's keys
+        let template = start.cloned().unwrap_or_else(Keyboard::new_47).on(geometry.clone());
+        let pinned = config.moves.pin(&template)?;
+        if let Err(e) = pinned.validate() {
+            return Err(ConfigError::Invalid(format!("the start layout is invalid: {}", e)))
+        }
+        if let Some(constraint) = pinned.unmet_constraints().first().filter(|_| start.is_some()) {
+            return Err(ConfigError::Invalid(format!("the start layout breaks the constraint {}", constraint)))
+        }
+        // random layouts are fixed up to meet the constraints, which has to be possible
+        if let Err(constraint) = pinned.clone().constrained(&mut ChaCha8Rng::seed_from_u64(seed)) {
+            return Err(ConfigError::Invalid(format!("no layout meets the constraint {}", constraint)))
+        }
+        let start = start.map(|_| pinned);
+        Ok(Self { config, geometry, start, groups })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CheckpointError> {
+        let checkpoint: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        checkpoint.check()?;
+        Ok(checkpoint)
+    }
+
+    /// Whether the checkpoint fits its config: a valid config, a state for
+    /// every group and, for groups under way, a full population and a full
+    /// window of scores.
+    fn check(&self) -> Result<(), CheckpointError> {
+        let invalid = |msg: String| Err(CheckpointError::Invalid(msg));
+        let config = &self.config;
+
+        if let Err(e) = config.validate() { return invalid(e.to_string()) }
+        if self.groups.len() != config.groups {
+            return invalid(format!("{} groups saved but the config has {}", self.groups.len(), config.groups))
+        }
+        let started = self.groups.iter().enumerate().filter(|(_, g)| !g.population.is_empty() && g.result.is_none());
+        for (i, group) in started {
+            if group.population.len() != config.population {
+                return invalid(format!(
+                    "group {} has {} keyboards but the population is {}",
+                    i + 1, group.population.len(), config.population,
+                ))
+            }
+            if group.score_history.len() != config.stagnation_window {
+                return invalid(format!(
+                    "group {} has {} past scores but the stagnation window is {}",
+                    i + 1, group.score_history.len(), config.stagnation_window,
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the checkpoint next to `path` first and then moves it into
+    /// place, so an interruption never leaves a half written file behind.
+    pub fn save(&self, path: &Path) -> Result<(), CheckpointError> {
+        write(path, &serde_json::to_string(self)?)
+    }
+}
+
+/// Writes `text` to a file next to `path` and moves it into place.
+fn write(path: &Path, text: &str) -> Result<(), CheckpointError> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// The file form of a checkpoint. Keyboards are kept as layout strings and
+/// read back with the checkpoint's geometry.
+#[derive(Serialize, Deserialize)]
+struct CheckpointFile<G = GroupFile> {
+    config: GaConfig,
+    geometry: Geometry,
+    start: Option<String>,
+    groups: Vec<G>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GroupFile {
+    generation: usize,
+    population: Vec<String>,
+    top: Vec<(f32, String)>,
+    score_history: Vec<f32>,
+    rng: RngState,
+    result: Option<(f32, String)>,
+}
+
+impl From<&GroupState> for GroupFile {
+    fn from(group: &GroupState) -> Self {
+        let scored = |(score, k): &(f32, Keyboard)| (*score, k.to_layout_string());
+        GroupFile {
+            generation: group.generation,
+            population: group.population.iter().map(Keyboard::to_layout_string).collect(),
+            top: group.top.iter().map(scored).collect(),
+            score_history: group.score_history.clone(),
+            rng: group.rng.clone(),
+            result: group.result.as_ref().map(scored),
+        }
+    }
+}
+
+impl Checkpoint {
+    /// The file form, with `groups` standing for the groups.
+    fn to_file<G>(&self, groups: Vec<G>) -> CheckpointFile<G> {
+        CheckpointFile {
+            config: self.config.clone(),
+            geometry: (*self.geometry).clone(),
+            start: self.start.as_ref().map(Keyboard::to_layout_string),
+            groups,
+        }
+    }
+}
+
+impl Serialize for Checkpoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_file(self.groups.iter().map(GroupFile::from).collect()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Checkpoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let file = CheckpointFile::<GroupFile>::deserialize(deserializer)?;
+        let geometry = Arc::new(file.geometry);
+        let layout = |s: String| Keyboard::parse(&s, geometry.clone());
+        let scored = |(score, s): (f32, String)| Ok::<_, LayoutError>((score, layout(s)?));
+
+        let groups = file.groups
+            .into_iter()
+            .map(|group| Ok(GroupState {
+                generation: group.generation,
+                population: group.population.into_iter().map(layout).collect::<Result<_, _>>()?,
+                top: group.top.into_iter().map(scored).collect::<Result<_, _>>()?,
+                score_history: group.score_history,
+                rng: group.rng,
+                result: group.result.map(scored).transpose()?,
+            }))
+            .collect::<Result<_, LayoutError>>()
+            .map_err(de::Error::custom)?;
+
+        Ok(Checkpoint {
+            config: file.config,
+            start: file.start.map(layout).transpose().map_err(de::Error::custom)?,
+            geometry,
+            groups,
+        })
+    }
+}
+
+/// Keeps the checkpoint file of a running search up to date. Each group is
+/// turned into JSON by the thread running it and the file is put together
+/// from those pieces, so a save never waits on other groups being
+/// serialized. Only one thread writes at a time; a group saving while the
+/// file is being written leaves its state for that thread to write next.
+pub(crate) struct Saver<'a> {
+    path: &'a Path,
+    pending: Mutex<Pending>,
+    writing: Mutex<()>,
+}
+
+struct Pending {
+    file: CheckpointFile<Box<RawValue>>,
+    /// Whether a group changed since the file was last written.
+    changed: bool,
+}
+
+impl<'a> Saver<'a> {
+    pub fn new(checkpoint: &Checkpoint, path: &'a Path) -> Result<Self, CheckpointError> {
+        let groups = checkpoint.groups
+            .iter()
+            .map(|group| to_raw_value(&GroupFile::from(group)))
+            .collect::<Result<_, _>>()?;
+        let file = checkpoint.to_file(groups);
+        Ok(Self { path, pending: Mutex::new(Pending { file, changed: false }), writing: Mutex::new(()) })
+    }
+
+    /// Records `state` as the state of `group` and writes the checkpoint.
+    pub fn save(&self, group: usize, state: &GroupState) -> Result<(), CheckpointError> {
+        let raw = to_raw_value(&GroupFile::from(state))?;
+        {
+            let mut pending = self.pending.lock().unwrap();
+            pending.file.groups[group] = raw;
+            pending.changed = true;
+        }
+
+        loop {
+            let Ok(writing) = self.writing.try_lock() else { return Ok(()) };
+            let text = {
+                let mut pending = self.pending.lock().unwrap();
+                match std::mem::take(&mut pending.changed) {
+                    true => Some(serde_json::to_string(&pending.file)?),
+                    false => None,
+                }
+            };
+            if let Some(text) = text { write(self.path, &text)? }
+            drop(writing);
+            // a group may have saved after the file was put together
+            if !self.pending.lock().unwrap().changed { return Ok(()) }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::{collections::BTreeMap, fmt, fs, io, path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Constraint, Geometry, Keyboard, ScoreWeights};
+
+/// Every knob of the genetic search. Load one from a TOML file with
+/// [`GaConfig::from_file`] so a run can be committed and repeated exactly.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GaConfig {
+    /// Independent groups evolved in parallel; the best keyboard of each is returned.
+    pub groups: usize,
+    /// Keyboards scored in every generation of a group.
+    pub population: usize,
+    /// Best keyboards kept each generation; the rest of the population are their children.
+    pub survivors: usize,
+    /// Swaps applied to each child, cycled through in order as children are bred.
+    pub mutation_schedule: Vec<usize>,
+    /// A group stops once its best score hasn't changed for this many generations.
+    pub stagnation_window: usize,
+    /// Seed for every random choice of the run. The same seed, config and
+    /// corpus always give the same result. Picked at random when unset.
+    pub seed: Option<u64>,
+    /// Generations between checkpoint saves of a group, when saving is on.
+    pub checkpoint_interval: usize,
+    /// Weights of the built-in metrics the search minimizes.
+    pub weights: ScoreWeights,
+    /// Keys the search may move and keys it must leave in place.
+    pub moves: MoveConfig,
+}
+
+/// Which positions the optimizer may use, the `[moves]` table of a run
+/// config. Positions count row by row from the top left of the geometry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MoveConfig {
+    /// Positions keys may be swapped between, in place of the geometry's.
+    /// A run with a start layout moves that layout's letters if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub movable: Option<Vec<usize>>,
+    /// Movable positions punctuation may be moved to, in place of the
+    /// geometry's. Those of the geometry's still movable if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub punctuation: Option<Vec<usize>>,
+    /// Characters kept at a position. They are moved there from wherever the
+    /// starting layout has them and never moved again.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pinned: BTreeMap<char, usize>,
+    /// Rules every layout of the run keeps, on top of the geometry's.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<Constraint>,
+}
+
+impl MoveConfig {
+    /// `geometry` with these move rules.
+    pub fn apply(&self, geometry: &Geometry) -> Result<Arc<Geometry>, ConfigError> {
+        match geometry.with_moves(
+            self.movable.clone(),
+            self.punctuation.clone(),
+            self.pinned.clone(),
+            self.constraints.clone(),
+        ) {
+            Ok(geometry) => Ok(Arc::new(geometry)),
+            Err(e) => Err(ConfigError::Invalid(e.to_string())),
+        }
+    }
+
+    /// `keyboard` with every pinned character moved to its position.
+    pub fn pin(&self, keyboard: &Keyboard) -> Result<Keyboard, ConfigError> {
+        let mut keyboard = keyboard.clone();
+        for (&c, &position) in &self.pinned {
+            let Some(from) = keyboard.keys.iter().position(|k| k.match_char(c)) else {
+                return Err(ConfigError::Invalid(format!("pinned `{}` is not on the keyboard", c)))
+            };
+            if position >= keyboard.keys.len() {
+                return Err(ConfigError::Invalid(format!("`{}` is pinned to key {} but there are {} keys", c, position, keyboard.keys.len())))
+            }
+            keyboard.keys.swap(from, position);
+        }
+        Ok(keyboard)
+    }
+}
+
+impl Default for GaConfig {
+    fn default() -> Self {
+        Self {
+            groups: 100,
+            population: 100,
+            survivors: 50,
+            mutation_schedule: vec![1, 2, 4, 8, 16, 32],
+            stagnation_window: 100,
+            seed: None,
+            checkpoint_interval: 10,
+            weights: ScoreWeights::default(),
+            moves: MoveConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "could not read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "could not parse config: {}", e),
+            ConfigError::Invalid(msg) => write!(f, "invalid config: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self { ConfigError::Io(e) }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self { ConfigError::Parse(e) }
+}
+
+impl GaConfig {
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("GaConfig is always representable as TOML")
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: &str| Err(ConfigError::Invalid(msg.to_string()));
+
+        if self.groups == 0 { return invalid("groups must be at least 1") }
+        if self.population == 0 { return invalid("population must be at least 1") }
+        if self.survivors == 0 || self.survivors > self.population {
+            return invalid("survivors must be between 1 and population")
+        }
+        if self.mutation_schedule.is_empty() { return invalid("mutation_schedule must not be empty") }
+        if self.mutation_schedule.contains(&0) { return invalid("mutation_schedule entries must be at least 1") }
+        if self.stagnation_window == 0 { return invalid("stagnation_window must be at least 1") }
+        if self.checkpoint_interval == 0 { return invalid("checkpoint_interval must be at least 1") }
+        if self.weights.all().iter().any(|w| !w.is_finite()) { return invalid("weights must be finite") }
+        let mut pinned: Vec<usize> = self.moves.pinned.values().copied().collect();
+        pinned.sort();
+        if pinned.windows(2).any(|w| w[0] == w[1]) { return invalid("two keys are pinned to one position") }
+        if let (Some(movable), Some(punctuation)) = (&self.moves.movable, &self.moves.punctuation) {
+            if punctuation.iter().any(|p| !movable.contains(p)) {
+                return invalid("punctuation positions must be movable")
+            }
+        }
+        if self.weights.finger_max_load.values().any(|&max| !(0. ..=100.).contains(&max)) {
+            return invalid("finger_max_load values must be percentages between 0 and 100")
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Placement rules a layout has to keep, such as a key staying on one hand
+//! or two keys sitting side by side. Random layouts are made to meet them
+//! and the optimizer never makes a swap that breaks one.
+
+use std::fmt;
+
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{Geometry, Hand, Key, Keyboard};
+
+/// One placement rule, written in TOML as a table whose `rule` names the
+/// kind, e.g. `{ rule = "hand", key = "e", hand = "Left" }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Constraint {
+    /// The key typing `key` is on `hand`.
+    Hand { key: char, hand: Hand },
+    /// The key typing `key` is on `row`, counted from the top in the order
+    /// the geometry lists its rows (see `Geometry::row_of`).
+    Row { key: char, row: usize },
+    /// The keys typing the two characters sit side by side: about a key
+    /// width apart and at about the same height, so not across the gap of
+    /// a split board.
+    Adjacent { keys: [char; 2] },
+    /// `positions` only ever hold letters.
+    LettersOnly { positions: Vec<usize> },
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::Hand { key, hand: Hand::Left } => write!(f, "`{}` on the left hand", key),
+            Constraint::Hand { key, hand: Hand::Right } => write!(f, "`{}` on the right hand", key),
+            Constraint::Row { key, row } => write!(f, "`{}` on row {}", key, row),
+            Constraint::Adjacent { keys: [a, b] } => write!(f, "`{}` next to `{}`", a, b),
+            Constraint::LettersOnly { positions } => write!(f, "only letters at keys {:?}", positions),
+        }
+    }
+}
+
+impl Constraint {
+    /// Whether the rule can apply to `geometry` at all. Positions are
+    /// checked with the rest of the geometry's moves.
+    pub(crate) fn check(&self, geometry: &Geometry) -> Result<(), String> {
+        match self {
+            Constraint::Row { row, .. } if *row >= geometry.row_count() =>
+                Err(format!("constraint {} but there are {} rows", self, geometry.row_count())),
+            Constraint::Adjacent { keys: [a, b] } if a == b => Err(format!("constraint {} names one key twice", self)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Positions the rule names directly.
+    pub(crate) fn positions(&self) -> &[usize] {
+        match self {
+            Constraint::LettersOnly { positions } => positions,
+            _ => &[],
+        }
+    }
+
+    pub fn holds(&self, keyboard: &Keyboard) -> bool {
+        let geometry = keyboard.geometry();
+        let at = |c: char| keyboard.keys().iter().position(|k| k.match_char(c));
+        match *self {
+            Constraint::Hand { key, hand } => at(key).and_then(|i| geometry.hand_of(i)) == Some(hand),
+            Constraint::Row { key, row } => at(key).and_then(|i| geometry.row_of(i)) == Some(row),
+            Constraint::Adjacent { keys: [a, b] } => match (at(a).and_then(|i| geometry.position(i)), at(b).and_then(|i| geometry.position(i))) {
+                (Some((ax, ay)), Some((bx, by))) => (0.5..1.5).contains(&(ax - bx).abs()) && (ay - by).abs() < 0.5,
+                _ => false,
+            },
+            Constraint::LettersOnly { ref positions } =>
+                positions.iter().all(|&i| matches!(keyboard.keys()[i], Key::Letter(_, _))),
+        }
+    }
+
+    /// Positions whose keys have to move for the rule to hold again.
+    fn culprits(&self, keyboard: &Keyboard) -> Vec<usize> {
+        let at = |c: &char| keyboard.keys().iter().position(|k| k.match_char(*c));
+        match self {
+            Constraint::Hand { key, .. } | Constraint::Row { key, .. } => at(key).into_iter().collect(),
+            Constraint::Adjacent { keys } => keys.iter().filter_map(at).collect(),
+            Constraint::LettersOnly { positions } => positions
+                .iter()
+                .copied()
+                .filter(|&i| !matches!(keyboard.keys()[i], Key::Letter(_, _)))
+                .collect(),
+        }
+    }
+}
+
+impl Keyboard {
+    /// The geometry's constraints this keyboard breaks.
+    pub fn unmet_constraints(&self) -> Vec<&Constraint> {
+        self.geometry().moves().constraints.iter().filter(|c| !c.holds(self)).collect()
+    }
+
+    pub fn meets_constraints(&self) -> bool {
+        self.geometry().moves().constraints.iter().all(|c| c.holds(self))
+    }
+
+    /// This keyboard with movable keys swapped until every constraint holds,
+    /// or the first constraint no swaps could meet.
+    pub(crate) fn constrained(mut self, rng: &mut impl Rng) -> Result<Keyboard, Constraint> {
+        let thumbs = self.thumb_keys();
+        let movable: Vec<usize> = self.movable().into_iter().filter(|i| !thumbs.contains(i)).collect();
+        for _ in 0..MAX_FIXES {
+            let unmet = self.unmet_constraints().len();
+            let Some(constraint) = self.unmet_constraints().first().map(|&c| c.clone()) else { return Ok(self) };
+
+            // swaps of a culprit that meet the rule without breaking another,
+            // or else ones that at least break nothing new
+            let (mut meeting, mut neutral) = (vec![], vec![]);
+            for a in constraint.culprits(&self).into_iter().filter(|a| movable.contains(a)) {
+                for &b in movable.iter().filter(|&&b| b != a) {
+                    self.keys.swap(a, b);
+                    let now = self.unmet_constraints().len();
+                    if constraint.holds(&self) && now < unmet { meeting.push((a, b)) }
+                    else if now <= unmet { neutral.push((a, b)) }
+                    self.keys.swap(a, b);
+                }
+            }
+            let swaps = if meeting.is_empty() { neutral } else { meeting };
+            let Some(&(a, b)) = swaps.choose(rng) else { return Err(constraint) };
+            self.keys.swap(a, b);
+        }
+        match self.unmet_constraints().first() {
+            Some(&constraint) => Err(constraint.clone()),
+            None => Ok(self),
+        }
+    }
+}
+
+/// Swaps [`Keyboard::constrained`] tries before giving up.
+const MAX_FIXES: usize = 1000;
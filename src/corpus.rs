@@ -0,0 +1,207 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::*;
+
+/// N-gram counts of a body of text. Built once and then used to score any
+/// number of keyboards.
+///
+/// Grams never span two files. The tables are ordered so that summing over
+/// them always happens in the same order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Corpus {
+    unigrams: BTreeMap<char, u64>,
+    bigrams: BTreeMap<[char; 2], u64>,
+    trigrams: BTreeMap<[char; 3], u64>,
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry_path = entry?.path();
+
+        if entry_path.is_file() {
+            files.push(entry_path);
+        } else if entry_path.is_dir() {
+            collect_files(&entry_path, files)?;
+        }
+    }
+    Ok(())
+}
+
+fn corpus_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    collect_files(path, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// 64-bit FNV-1a. Used instead of `DefaultHasher` because cache keys have to
+/// stay the same across builds and Rust releases.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self { Fnv(0xcbf29ce484222325) }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Bump whenever the tables or the file layout change so old caches are ignored.
+const CACHE_VERSION: u32 = 1;
+const CACHE_MAGIC: &[u8; 8] = b"KBGCRPUS";
+
+impl Corpus {
+    /// Reads every file under `path`, recursively. Files that aren't valid
+    /// UTF-8 are skipped.
+    pub fn from_dir(path: &Path) -> io::Result<Self> {
+        Ok(Self::from_files(&corpus_files(path)?))
+    }
+
+    fn from_files(files: &[PathBuf]) -> Self {
+        files
+            .par_iter()
+            .filter_map(|file| fs::read_to_string(file).ok())
+            .map(|contents| Corpus::from_text(&contents))
+            .reduce(Corpus::default, |mut a, b| { a.merge(b); a })
+    }
+
+    /// Like [`Corpus::from_dir`], but keeps the tables in `cache_dir` under a
+    /// hash of the corpus contents. A later run over the same files loads them
+    /// from there instead of scanning the text again.
+    pub fn from_dir_cached(path: &Path, cache_dir: &Path) -> io::Result<Self> {
+        let files = corpus_files(path)?;
+        let cache_file = cache_dir.join(format!("{:016x}.corpus", Self::content_hash(&files)?));
+
+        if let Ok(corpus) = Self::read_cache(&cache_file) {
+            return Ok(corpus)
+        }
+
+        let corpus = Self::from_files(&files);
+        fs::create_dir_all(cache_dir)?;
+        let tmp = cache_file.with_extension("tmp");
+        corpus.write_cache(&tmp)?;
+        fs::rename(tmp, cache_file)?;
+        Ok(corpus)
+    }
+
+    /// Hash of every file's bytes, read a chunk at a time so a large corpus
+    /// is never held in memory just to find its cache.
+    fn content_hash(files: &[PathBuf]) -> io::Result<u64> {
+        let file_hashes = files
+            .par_iter()
+            .map(|file| {
+                let mut hash = Fnv::new();
+                let mut file = fs::File::open(file)?;
+                let mut chunk = vec![0; 64 * 1024];
+                loop {
+                    match file.read(&mut chunk) {
+                        Ok(0) => break Ok(hash.0),
+                        Ok(n) => hash.write(&chunk[..n]),
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                        Err(e) => break Err(e),
+                    }
+                }
+            })
+            .collect::<io::Result<Vec<u64>>>()?;
+
+        let mut hash = Fnv::new();
+        hash.write(&CACHE_VERSION.to_le_bytes());
+        for h in file_hashes {
+            hash.write(&h.to_le_bytes());
+        }
+        Ok(hash.0)
+    }
+
+    fn write_cache(&self, path: &Path) -> io::Result<()> {
+        fn table<const N: usize>(w: &mut impl Write, t: &BTreeMap<[char; N], u64>) -> io::Result<()> {
+            w.write_all(&(t.len() as u64).to_le_bytes())?;
+            for (gram, &n) in t {
+                for &c in gram { w.write_all(&(c as u32).to_le_bytes())? }
+                w.write_all(&n.to_le_bytes())?;
+            }
+            Ok(())
+        }
+
+        let mut w = BufWriter::new(fs::File::create(path)?);
+        w.write_all(CACHE_MAGIC)?;
+        w.write_all(&CACHE_VERSION.to_le_bytes())?;
+        let unigrams: BTreeMap<[char; 1], u64> = self.unigrams.iter().map(|(&c, &n)| ([c], n)).collect();
+        table(&mut w, &unigrams)?;
+        table(&mut w, &self.bigrams)?;
+        table(&mut w, &self.trigrams)?;
+        w.flush()
+    }
+
+    fn read_cache(path: &Path) -> io::Result<Self> {
+        fn bad() -> io::Error { io::Error::new(io::ErrorKind::InvalidData, "corrupt corpus cache") }
+        fn u32_(r: &mut impl Read) -> io::Result<u32> {
+            let mut b = [0; 4];
+            r.read_exact(&mut b)?;
+            Ok(u32::from_le_bytes(b))
+        }
+        fn u64_(r: &mut impl Read) -> io::Result<u64> {
+            let mut b = [0; 8];
+            r.read_exact(&mut b)?;
+            Ok(u64::from_le_bytes(b))
+        }
+        fn table<const N: usize>(r: &mut impl Read) -> io::Result<BTreeMap<[char; N], u64>> {
+            let mut t = BTreeMap::new();
+            for _ in 0..u64_(r)? {
+                let mut gram = ['\0'; N];
+                for c in gram.iter_mut() {
+                    *c = char::from_u32(u32_(r)?).ok_or_else(bad)?;
+                }
+                t.insert(gram, u64_(r)?);
+            }
+            Ok(t)
+        }
+
+        let mut r = BufReader::new(fs::File::open(path)?);
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != CACHE_MAGIC || u32_(&mut r)? != CACHE_VERSION { return Err(bad()) }
+
+        let unigrams = table::<1>(&mut r)?.into_iter().map(|([c], n)| (c, n)).collect();
+        let bigrams = table(&mut r)?;
+        let trigrams = table(&mut r)?;
+        if r.read(&mut [0])? != 0 { return Err(bad()) }
+        Ok(Corpus { unigrams, bigrams, trigrams })
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        let mut corpus = Corpus::default();
+        corpus.add_text(text);
+        corpus
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        let chars: Vec<char> = text.chars().collect();
+        for &c in chars.iter() {
+            *self.unigrams.entry(c).or_default() += 1;
+        }
+        for w in chars.windows(2) {
+            *self.bigrams.entry([w[0], w[1]]).or_default() += 1;
+        }
+        for w in chars.windows(3) {
+            *self.trigrams.entry([w[0], w[1], w[2]]).or_default() += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: Corpus) {
+        for (k, n) in other.unigrams { *self.unigrams.entry(k).or_default() += n }
+        for (k, n) in other.bigrams { *self.bigrams.entry(k).or_default() += n }
+        for (k, n) in other.trigrams { *self.trigrams.entry(k).or_default() += n }
+    }
+
+    pub fn unigrams(&self) -> &BTreeMap<char, u64> { &self.unigrams }
+    pub fn bigrams(&self) -> &BTreeMap<[char; 2], u64> { &self.bigrams }
+    pub fn trigrams(&self) -> &BTreeMap<[char; 3], u64> { &self.trigrams }
+}
@@ -0,0 +1,196 @@
+//! What changed between two keyboards: which keys moved, the swaps and
+//! cycles that move them, the characters moved on and off other layers, and
+//! the two drawn side by side.
+
+use std::{collections::BTreeMap, fmt};
+
+use colored::Colorize;
+
+use crate::{layout, Key, Keyboard};
+
+/// How to get from one keyboard to another typing the same characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutDiff {
+    /// Every key in a different position: the key, where it was and where
+    /// it is now.
+    pub moved: Vec<(Key, usize, usize)>,
+    /// The positions of every group of keys that went round in a circle,
+    /// each key taking the place of the next and the last that of the first.
+    /// A swap is a cycle of two. Keys moved to where a key changed
+    /// characters are only in `moved`.
+    pub cycles: Vec<Vec<usize>>,
+    /// Every character that moved other than with its key, such as symbols
+    /// moved on, off or within a layer: the character, where it was and
+    /// where it is now.
+    pub chars_moved: Vec<(char, Place, Place)>,
+    /// Positions, on the base layer or any other, holding something else.
+    changed: usize,
+    /// Base-layer labels of the first keyboard, to name keys by.
+    labels: Vec<String>,
+    /// Names of the first keyboard's layers.
+    layers: Vec<String>,
+}
+
+/// Where a character is typed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Place {
+    Base(usize),
+    Shifted(usize),
+    /// A position on the layer of that index.
+    Layer(usize, usize),
+}
+
+impl LayoutDiff {
+    /// Number of positions, on the base layer or any other, holding
+    /// something else.
+    pub fn changed(&self) -> usize {
+        self.changed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed == 0
+    }
+
+    fn place_label(&self, place: Place) -> String {
+        match place {
+            Place::Base(i) => i.to_string(),
+            Place::Shifted(i) => format!("{} shifted", i),
+            Place::Layer(layer, i) => format!("@{} {}", self.layers[layer], i),
+        }
+    }
+}
+
+impl fmt::Display for LayoutDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let positions = self.labels.len() * (1 + self.layers.len());
+        write!(f, "{} of {} positions changed", self.changed(), positions)?;
+        for cycle in &self.cycles {
+            let keys: Vec<&str> = cycle.iter().map(|&i| self.labels[i].as_str()).collect();
+            match keys[..] {
+                [a, b] => write!(f, "\n  swap {} {}", a, b)?,
+                _ => write!(f, "\n  cycle {} -> {}", keys.join(" -> "), keys[0])?,
+            }
+        }
+        for &(_, from, to) in &self.moved {
+            if !self.cycles.iter().any(|cycle| cycle.contains(&from)) {
+                write!(f, "\n  move {} {} -> {}", self.labels[from], from, to)?;
+            }
+        }
+        for &(c, from, to) in &self.chars_moved {
+            write!(f, "\n  move {} {} -> {}", layout::label(c), self.place_label(from), self.place_label(to))?;
+        }
+        Ok(())
+    }
+}
+
+impl Keyboard {
+    /// How this keyboard changes into `other`, or `None` if they don't type
+    /// the same characters on the same number of layers.
+    pub fn diff(&self, other: &Keyboard) -> Option<LayoutDiff> {
+        if self.keys.len() != other.keys.len() || self.layers.len() != other.layers.len() { return None }
+        let (places, other_places) = (self.places(), other.places());
+        if !places.keys().eq(other_places.keys()) { return None }
+
+        // where each changed position's key went, taking identical keys in
+        // order; keys whose characters changed on the way aren't followed
+        let mut to = vec![None; self.keys.len()];
+        let mut taken = vec![false; self.keys.len()];
+        for (from, key) in self.keys.iter().enumerate().filter(|&(i, key)| other.keys[i] != *key) {
+            let target = (0..other.keys.len())
+                .find(|&j| !taken[j] && other.keys[j] == *key && self.keys[j] != other.keys[j]);
+            if let Some(target) = target {
+                taken[target] = true;
+                to[from] = Some(target);
+            }
+        }
+
+        let moved: Vec<(Key, usize, usize)> = to
+            .iter()
+            .enumerate()
+            .filter_map(|(from, to)| to.map(|to| (self.keys[from], from, to)))
+            .collect();
+
+        let mut cycles = vec![];
+        let mut seen = vec![false; self.keys.len()];
+        for start in 0..self.keys.len() {
+            let mut cycle = vec![];
+            let mut at = start;
+            while let (false, Some(next)) = (seen[at], to[at]) {
+                seen[at] = true;
+                cycle.push(at);
+                at = next;
+            }
+            // keys moved to where a key changed characters don't come round
+            if !cycle.is_empty() && at == start { cycles.push(cycle) }
+        }
+
+        let with_key = |from: Place, to: Place| match (from, to) {
+            (Place::Base(a), Place::Base(b)) | (Place::Shifted(a), Place::Shifted(b)) =>
+                moved.iter().any(|&(_, from, to)| (from, to) == (a, b)),
+            _ => false,
+        };
+        let chars_moved = places
+            .iter()
+            .map(|(&c, &from)| (c, from, other_places[&c]))
+            .filter(|&(_, from, to)| from != to && !with_key(from, to))
+            .collect();
+
+        let changed = (0..self.keys.len()).filter(|&i| self.keys[i] != other.keys[i]).count()
+            + self.layers
+                .iter()
+                .zip(&other.layers)
+                .map(|(a, b)| a.keys.iter().zip(&b.keys).filter(|(a, b)| a != b).count())
+                .sum::<usize>();
+
+        let labels = (0..self.keys.len()).map(|i| self.key_label(i)).collect();
+        let layers = self.layers.iter().map(|layer| layer.name.clone()).collect();
+        Some(LayoutDiff { moved, cycles, chars_moved, changed, labels, layers })
+    }
+
+    /// Where every character on the keyboard is typed.
+    fn places(&self) -> BTreeMap<char, Place> {
+        let mut places = BTreeMap::new();
+        for (i, key) in self.keys.iter().enumerate() {
+            let Some((base, shift)) = key.chars() else { continue };
+            places.insert(base, Place::Base(i));
+            if shift != base { places.entry(shift).or_insert(Place::Shifted(i)); }
+        }
+        for (l, layer) in self.layers.iter().enumerate() {
+            for (i, &c) in layer.keys.iter().enumerate() {
+                if let Some(c) = c { places.entry(c).or_insert(Place::Layer(l, i)); }
+            }
+        }
+        places
+    }
+
+    /// This keyboard and `other` drawn next to each other, with the
+    /// positions holding different keys highlighted.
+    pub fn side_by_side(&self, other: &Keyboard) -> String {
+        let changed = |i: usize| self.keys.get(i) != other.keys.get(i)
+            || self.layers.iter().zip(&other.layers).any(|(a, b)| a.keys.get(i) != b.keys.get(i));
+        let style = |i: usize, label: String| match changed(i) {
+            true => label.yellow().bold().to_string(),
+            false => label,
+        };
+
+        let plain = self.grid(|i| self.key_label(i));
+        let width = plain.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let left = self.styled_grid(|i| self.key_label(i), style);
+        let right = other.styled_grid(|i| other.key_label(i), style);
+
+        let mut text = String::new();
+        for ((plain, left), right) in plain.lines().zip(left.lines()).zip(right.lines()) {
+            let padding = width - plain.chars().count();
+            text.push_str(&format!("{}{}    {}\n", left, " ".repeat(padding), right));
+        }
+        text
+    }
+
+    /// How the key at `i` is drawn.
+    pub(crate) fn key_label(&self, i: usize) -> String {
+        match self.keys[i] {
+            Key::Layer(layer) => self.access_label(layer),
+            key => key.key_val(),
+        }
+    }
+}
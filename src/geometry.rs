@@ -0,0 +1,492 @@
+//! The physical shape of a keyboard: how many rows it has, how many keys sit
+//! in each, where they are and which finger types them.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Constraint;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Finger {
+    // left pinky through right pinky, in the order they sit on the board
+    LPinky,
+    LRing,
+    LMid,
+    LIndex,
+    LThumb,
+
+    RThumb,
+    RIndex,
+    RMid,
+    RRing,
+    RPinky,
+}
+
+impl Finger {
+    pub const ALL: [Finger; 10] = [
+        Finger::LPinky, Finger::LRing, Finger::LMid, Finger::LIndex, Finger::LThumb,
+        Finger::RThumb, Finger::RIndex, Finger::RMid, Finger::RRing, Finger::RPinky,
+    ];
+
+    pub fn hand(self) -> Hand {
+        if self <= Finger::LThumb { Hand::Left } else { Hand::Right }
+    }
+
+    pub fn is_thumb(self) -> bool {
+        matches!(self, Finger::LThumb | Finger::RThumb)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+impl Hand {
+    pub fn other(self) -> Hand {
+        match self {
+            Hand::Left => Hand::Right,
+            Hand::Right => Hand::Left,
+        }
+    }
+
+    pub fn pinky(self) -> Finger {
+        match self {
+            Hand::Left => Finger::LPinky,
+            Hand::Right => Finger::RPinky,
+        }
+    }
+}
+
+/// Which keys the optimizer may move, the `[moves]` table of a geometry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct MoveRules {
+    /// Positions whose keys may be swapped; those holding letters if not
+    /// given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub movable: Option<Vec<usize>>,
+    /// Movable positions punctuation may be moved to; any if empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub punctuation: Vec<usize>,
+    /// Characters kept at a position, whatever else says it may move.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pinned: BTreeMap<char, usize>,
+    /// Rules every layout on the geometry has to keep.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<Constraint>,
+}
+
+impl MoveRules {
+    /// Positions that must always hold a letter.
+    pub fn letters_only(&self) -> Vec<usize> {
+        self.constraints
+            .iter()
+            .filter(|c| matches!(c, Constraint::LettersOnly { .. }))
+            .flat_map(|c| c.positions().iter().copied())
+            .collect()
+    }
+}
+
+/// What key coordinates are measured in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Units {
+    /// Key widths.
+    #[default]
+    Keys,
+    /// Millimetres, converted to key widths with the geometry's `key_size`.
+    Mm,
+}
+
+/// A row's y coordinate: one for the whole row, or one per key for column
+/// staggered boards.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum RowY {
+    Row(f32),
+    Keys(Vec<f32>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RowDef {
+    keys: usize,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    stagger: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    y: Option<RowY>,
+    heatmap: Vec<f32>,
+    fingers: Vec<Finger>,
+    /// Keys a layout gets when it leaves the row out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    defaults: Option<Vec<char>>,
+}
+
+fn is_zero(n: &f32) -> bool { *n == 0. }
+fn is_default<T: Default + PartialEq>(t: &T) -> bool { *t == T::default() }
+fn default_key_size() -> f32 { 19.05 }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GeometryDef {
+    name: String,
+    #[serde(default, skip_serializing_if = "is_default")]
+    units: Units,
+    /// Width of one key in millimetres.
+    #[serde(default = "default_key_size")]
+    key_size: f32,
+    /// The key each finger rests on.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    homes: BTreeMap<Finger, usize>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    moves: MoveRules,
+    rows: Vec<RowDef>,
+}
+
+/// A keyboard's rows and keys. Key positions are numbered row by row from
+/// the top left, the same order keys are listed in a layout file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "GeometryDef", try_from = "GeometryDef")]
+pub struct Geometry {
+    def: GeometryDef,
+    /// First key position of every row.
+    row_starts: Vec<usize>,
+    row: Vec<usize>,
+    /// Key positions, in key widths.
+    x: Vec<f32>,
+    y: Vec<f32>,
+    heatmap: Vec<f32>,
+    fingers: Vec<Finger>,
+    homes: BTreeMap<Finger, usize>,
+    defaults: Vec<Option<Vec<char>>>,
+}
+
+#[derive(Debug)]
+pub enum GeometryError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeometryError::Io(e) => write!(f, "could not read geometry: {}", e),
+            GeometryError::Parse(e) => write!(f, "could not parse geometry: {}", e),
+            GeometryError::Invalid(msg) => write!(f, "invalid geometry: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for GeometryError {}
+
+impl From<io::Error> for GeometryError {
+    fn from(e: io::Error) -> Self { GeometryError::Io(e) }
+}
+
+impl From<toml::de::Error> for GeometryError {
+    fn from(e: toml::de::Error) -> Self { GeometryError::Parse(e) }
+}
+
+impl From<Geometry> for GeometryDef {
+    fn from(geometry: Geometry) -> Self { geometry.def }
+}
+
+impl TryFrom<GeometryDef> for Geometry {
+    type Error = GeometryError;
+
+    fn try_from(def: GeometryDef) -> Result<Self, Self::Error> {
+        let invalid = |msg: String| Err(GeometryError::Invalid(msg));
+
+        if def.rows.is_empty() { return invalid("a geometry needs at least one row".into()) }
+        if !def.key_size.is_finite() || def.key_size <= 0. { return invalid("key_size must be positive".into()) }
+        let scale = match def.units {
+            Units::Keys => 1.,
+            Units::Mm => 1. / def.key_size,
+        };
+
+        let mut geometry = Geometry {
+            def: def.clone(),
+            row_starts: vec![],
+            row: vec![],
+            x: vec![],
+            y: vec![],
+            heatmap: vec![],
+            fingers: vec![],
+            homes: BTreeMap::new(),
+            defaults: vec![],
+        };
+        for (r, row) in def.rows.into_iter().enumerate() {
+            if row.keys == 0 { return invalid(format!("row {} has no keys", r)) }
+            if row.heatmap.len() != row.keys {
+                return invalid(format!("row {} has {} keys but {} heatmap values", r, row.keys, row.heatmap.len()))
+            }
+            if row.fingers.len() != row.keys {
+                return invalid(format!("row {} has {} keys but {} fingers", r, row.keys, row.fingers.len()))
+            }
+            if let Some(defaults) = row.defaults.as_ref().filter(|d| d.len() != row.keys) {
+                return invalid(format!("row {} has {} keys but {} defaults", r, row.keys, defaults.len()))
+            }
+            let x: Vec<f32> = match row.x {
+                Some(x) if x.len() != row.keys =>
+                    return invalid(format!("row {} has {} keys but {} x positions", r, row.keys, x.len())),
+                Some(x) => x.iter().map(|x| x * scale).collect(),
+                None => (0..row.keys).map(|i| row.stagger * scale + i as f32).collect(),
+            };
+            let y: Vec<f32> = match row.y {
+                Some(RowY::Keys(y)) if y.len() != row.keys =>
+                    return invalid(format!("row {} has {} keys but {} y positions", r, row.keys, y.len())),
+                Some(RowY::Keys(y)) => y.iter().map(|y| y * scale).collect(),
+                Some(RowY::Row(y)) => vec![y * scale; row.keys],
+                None => vec![r as f32; row.keys],
+            };
+            // keys further apart than a key's height can't overlap
+            let overlap = |i: usize| x[i + 1] < x[i] + 0.999 && (y[i + 1] - y[i]).abs() < 0.999;
+            if x.windows(2).any(|w| w[1] < w[0]) || (0..row.keys - 1).any(overlap) {
+                return invalid(format!("keys of row {} overlap or are out of order", r))
+            }
+
+            geometry.row_starts.push(geometry.row.len());
+            geometry.row.extend(std::iter::repeat_n(r, row.keys));
+            geometry.x.extend(x);
+            geometry.y.extend(y);
+            geometry.heatmap.extend(row.heatmap);
+            geometry.fingers.extend(row.fingers);
+            geometry.defaults.push(row.defaults);
+        }
+
+        for (&finger, &home) in def.homes.iter() {
+            if geometry.finger_of(home) != Some(finger) {
+                return invalid(format!("home key {} of {:?} is not typed by that finger", home, finger))
+            }
+        }
+        // fingers without a given home rest on their easiest key
+        for (i, &finger) in geometry.fingers.iter().enumerate() {
+            let home = geometry.homes.entry(finger).or_insert(i);
+            if geometry.heatmap[i] < geometry.heatmap[*home] { *home = i }
+        }
+        geometry.homes.extend(def.homes);
+
+        let moves = &def.moves;
+        let positions = moves.movable.iter().flatten()
+            .chain(&moves.punctuation)
+            .chain(moves.pinned.values())
+            .chain(moves.constraints.iter().flat_map(Constraint::positions));
+        if let Some(index) = positions.copied().find(|&i| i >= geometry.len()) {
+            return invalid(format!("moves name key {} but there are {} keys", index, geometry.len()))
+        }
+        for constraint in &moves.constraints {
+            constraint.check(&geometry).map_err(GeometryError::Invalid)?;
+        }
+        Ok(geometry)
+    }
+}
+
+const ANSI_47: &str = include_str!("../geometries/ansi_47.toml");
+
+impl Geometry {
+    /// The row-staggered 47 key board of `geometries/ansi_47.toml`.
+    pub fn ansi_47() -> Arc<Geometry> {
+        static GEOMETRY: OnceLock<Arc<Geometry>> = OnceLock::new();
+        GEOMETRY
+            .get_or_init(|| Arc::new(Geometry::from_toml(ANSI_47).expect("the built-in geometry is valid")))
+            .clone()
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, GeometryError> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, GeometryError> {
+        toml::from_str::<GeometryDef>(text)?.try_into()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.def.name
+    }
+
+    /// Number of keys.
+    pub fn len(&self) -> usize {
+        self.row.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.row.is_empty()
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.row_starts.len()
+    }
+
+    /// Key positions of row `row`.
+    pub fn row_range(&self, row: usize) -> std::ops::Range<usize> {
+        let end = self.row_starts.get(row + 1).copied().unwrap_or(self.len());
+        self.row_starts[row]..end
+    }
+
+    /// The `[[rows]]` entry a key is listed in. That is not always the row
+    /// it sits on: `ansi_47` lists tab, space, enter and backspace as a
+    /// fifth row, so backspace is in row 4 here. `position` gives where a
+    /// key really is.
+    pub fn row_of(&self, index: usize) -> Option<usize> {
+        self.row.get(index).copied()
+    }
+
+    /// Place of a key in its listed row (see `row_of`), counting from the
+    /// left from 0.
+    pub fn column_of(&self, index: usize) -> Option<usize> {
+        Some(index - self.row_starts[self.row_of(index)?])
+    }
+
+    /// Horizontal position of a key, in key widths from the left edge.
+    pub fn x_of(&self, index: usize) -> Option<f32> {
+        self.x.get(index).copied()
+    }
+
+    /// Position of a key, in key widths from the top left.
+    pub fn position(&self, index: usize) -> Option<(f32, f32)> {
+        Some((*self.x.get(index)?, *self.y.get(index)?))
+    }
+
+    /// The key `finger` rests on.
+    pub fn home_of(&self, finger: Finger) -> Option<usize> {
+        self.homes.get(&finger).copied()
+    }
+
+    /// Straight line distance between two keys, in key widths.
+    pub fn travel(&self, a: usize, b: usize) -> Option<f32> {
+        let (ax, ay) = self.position(a)?;
+        let (bx, by) = self.position(b)?;
+        Some(((ax - bx).powi(2) + (ay - by).powi(2)).sqrt())
+    }
+
+    pub fn finger_of(&self, index: usize) -> Option<Finger> {
+        self.fingers.get(index).copied()
+    }
+
+    pub fn hand_of(&self, index: usize) -> Option<Hand> {
+        self.finger_of(index).map(Finger::hand)
+    }
+
+    /// Keys a layout gets for row `row` when it leaves the row out.
+    pub fn row_defaults(&self, row: usize) -> Option<&[char]> {
+        self.defaults.get(row)?.as_deref()
+    }
+
+    pub fn heatmap(&self) -> &[f32] {
+        &self.heatmap
+    }
+
+    /// Whether this is the built-in board, with any fingering and move
+    /// rules.
+    pub fn is_ansi_47(&self) -> bool {
+        let ansi = &Geometry::ansi_47().def;
+        let def = &self.def;
+        def.rows.len() == ansi.rows.len()
+            && def.rows.iter().zip(&ansi.rows).all(|(row, ansi)| RowDef { fingers: ansi.fingers.clone(), ..row.clone() } == *ansi)
+            && GeometryDef { rows: ansi.rows.clone(), homes: ansi.homes.clone(), moves: ansi.moves.clone(), ..def.clone() } == *ansi
+    }
+
+    pub(crate) fn moves(&self) -> &MoveRules {
+        &self.def.moves
+    }
+
+    /// This geometry with the optimizer limited to `movable` positions, if
+    /// given, keeping the `pinned` characters in place and keeping
+    /// `constraints` on top of its own.
+    pub fn with_moves(
+        &self,
+        movable: Option<Vec<usize>>,
+        punctuation: Option<Vec<usize>>,
+        pinned: BTreeMap<char, usize>,
+        constraints: Vec<Constraint>,
+    ) -> Result<Geometry, GeometryError> {
+        let mut def = self.def.clone();
+        if let Some(movable) = movable {
+            // punctuation positions no longer movable go with the old list
+            def.moves.punctuation.retain(|p| movable.contains(p));
+            def.moves.movable = Some(movable);
+        }
+        if let Some(punctuation) = punctuation { def.moves.punctuation = punctuation }
+        def.moves.pinned = pinned;
+        def.moves.constraints.extend(constraints);
+        def.try_into()
+    }
+
+    /// This geometry with the fingers of `fingering` in place of its own.
+    /// Home keys that end up under another finger are picked again.
+    pub fn with_fingering(&self, fingering: &Fingering) -> Result<Geometry, GeometryError> {
+        let mut def = self.def.clone();
+        for (&index, &finger) in &fingering.fingers {
+            let Some(row) = self.row_of(index) else {
+                return Err(GeometryError::Invalid(format!("fingering sets key {} but there are {} keys", index, self.len())))
+            };
+            def.rows[row].fingers[index - self.row_starts[row]] = finger;
+        }
+        let fingers: Vec<Finger> = def.rows.iter().flat_map(|row| row.fingers.iter().copied()).collect();
+        def.homes.retain(|&finger, &mut home| fingers.get(home) == Some(&finger));
+        def.try_into()
+    }
+}
+
+/// Which finger types which key, to try a different fingering of a geometry
+/// such as an angle mod. In a file it is a `[fingers]` table from key
+/// position to finger:
+///
+/// ```toml
+/// [fingers]
+/// 37 = "LRing"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "FingeringDef", into = "FingeringDef")]
+pub struct Fingering {
+    pub fingers: BTreeMap<usize, Finger>,
+}
+
+/// TOML keys are strings, so positions are kept as strings in files.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FingeringDef {
+    fingers: BTreeMap<String, Finger>,
+}
+
+impl TryFrom<FingeringDef> for Fingering {
+    type Error = GeometryError;
+
+    fn try_from(def: FingeringDef) -> Result<Self, Self::Error> {
+        let fingers = def.fingers
+            .into_iter()
+            .map(|(key, finger)| match key.parse() {
+                Ok(index) => Ok((index, finger)),
+                Err(_) => Err(GeometryError::Invalid(format!("fingering key `{}` is not a key position", key))),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Fingering { fingers })
+    }
+}
+
+impl From<Fingering> for FingeringDef {
+    fn from(fingering: Fingering) -> Self {
+        FingeringDef { fingers: fingering.fingers.into_iter().map(|(i, f)| (i.to_string(), f)).collect() }
+    }
+}
+
+impl Fingering {
+    pub fn from_file(path: &Path) -> Result<Self, GeometryError> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, GeometryError> {
+        Ok(toml::from_str(text)?)
+    }
+}
@@ -0,0 +1,60 @@
+//! Layers beyond base and shift, like a symbol or number layer, each reached
+//! by holding one of its access keys, a [`Key::Layer`] on the base layer.
+
+use crate::{Key, Keyboard};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Layer {
+    pub name: String,
+    /// The character at every position, `None` where the layer has none.
+    pub keys: Vec<Option<char>>,
+}
+
+impl Keyboard {
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// This keyboard with `layers` added. Every layer needs a slot for every
+    /// position of the geometry.
+    pub fn with_layers(mut self, layers: Vec<Layer>) -> Option<Self> {
+        if layers.iter().any(|layer| layer.keys.len() != self.keys.len()) { return None }
+        self.layers = layers;
+        Some(self)
+    }
+
+    /// The layer `c` is typed on, or `None` if it is on the base or shifted
+    /// layer or not on the keyboard at all.
+    pub fn layer_of(&self, c: char) -> Option<usize> {
+        if self.keys.iter().any(|k| k.match_char(c)) { return None }
+        self.layers.iter().position(|layer| layer.keys.contains(&Some(c)))
+    }
+
+    /// `layer_of` with the ASCII characters looked up ahead of time.
+    pub(crate) fn char_layer(&self) -> impl Fn(char) -> Option<usize> + '_ {
+        let mut ascii = [None; 128];
+        for (c, slot) in ascii.iter_mut().enumerate() {
+            *slot = self.layer_of(c as u8 as char);
+        }
+        move |c| if c.is_ascii() { ascii[c as usize] } else { self.layer_of(c) }
+    }
+
+    /// Positions of the keys that reach `layer`.
+    pub fn access_keys(&self, layer: usize) -> Vec<usize> {
+        (0..self.keys.len()).filter(|&i| self.keys[i] == Key::Layer(layer)).collect()
+    }
+
+    /// Positions of every layer the optimizer may move characters between:
+    /// all but the access keys, which are held while the layer is used.
+    pub(crate) fn layer_slots(&self) -> Vec<Vec<usize>> {
+        let spots: Vec<usize> = (0..self.keys.len())
+            .filter(|&i| !matches!(self.keys[i], Key::Layer(_)))
+            .collect();
+        vec![spots; self.layers.len()]
+    }
+
+    /// How an access key to `layer` is written, `@` and the layer's name.
+    pub(crate) fn access_label(&self, layer: usize) -> String {
+        format!("@{}", self.layers.get(layer).map_or("?", |l| l.name.as_str()))
+    }
+}
@@ -0,0 +1,318 @@
+//! Plain-text layout files.
+//!
+//! A layout file is the `print_self` grid: one line of whitespace separated
+//! keys for every row of the keyboard's [`Geometry`], 13, 13, 11 and 10 keys
+//! for the built-in one. It may be followed by a blank line and a second grid
+//! giving the shifted character of every key; without one the usual US shift
+//! pairs are assumed. Lines starting with `#` are comments, so a row starting
+//! with the `#` key writes it `\#`.
+//!
+//! Space, enter, tab and backspace are written `spc`, `ent`, `tab` and `bsp`.
+//! Trailing rows the geometry gives default keys for, like the thumb row of
+//! the built-in board, may be left out.
+//!
+//! Further [`Layer`]s follow as grids headed by a line with their name, like
+//! `@sym`. Positions with nothing on the layer are written `---`. A key
+//! written `@sym` on the base layer is held to reach that layer; the shifted
+//! and other grids repeat it, or leave the position empty.
+//!
+//! ```text
+//! `   1   2   3   4   5   6   7   8   9   0   ,   .
+//!       [   ]   i   o   -   =   f   n   w   v   q   z   \
+//!        a   r   t   e   c   h   j   k   l   s   g
+//!          ;   '   b   m   x   u   d   p   y   /
+//! ```
+
+use std::{fmt, fs, io, path::Path, str::FromStr, sync::Arc};
+
+use crate::{Geometry, Key, Keyboard, KeyboardError, Layer};
+
+#[derive(Debug)]
+pub enum LayoutError {
+    Io(io::Error),
+    /// A grid didn't have as many rows as the geometry, counting rows that
+    /// can be left out.
+    RowCount { layer: usize, expected: usize, found: usize },
+    /// A row didn't hold the number of keys it should.
+    KeyCount { layer: usize, row: usize, expected: usize, found: usize },
+    /// A key was more than one character.
+    BadKey { layer: usize, row: usize, token: String },
+    /// More than a base and a shifted grid were given before the named
+    /// layers.
+    TooManyLayers(usize),
+    /// A grid after a named layer had no name.
+    UnnamedLayer(usize),
+    DuplicateLayer(String),
+    /// An access key named a layer the file doesn't have.
+    UnknownLayer(String),
+    /// A named layer has no key on the base layer to reach it.
+    NoAccessKey(String),
+    /// A position of the base layer was left empty.
+    EmptyKey(usize),
+    /// No shifted grid was given and the character has no known shift pair.
+    UnknownShift(char),
+    /// The layout parsed but isn't a well formed keyboard.
+    Invalid(KeyboardError),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Io(e) => write!(f, "could not read layout: {}", e),
+            LayoutError::RowCount { layer, expected, found } =>
+                write!(f, "layer {} has {} rows, expected {}", layer, found, expected),
+            LayoutError::KeyCount { layer, row, expected, found } =>
+                write!(f, "layer {} row {} has {} keys, expected {}", layer, row, found, expected),
+            LayoutError::BadKey { layer, row, token } =>
+                write!(f, "layer {} row {}: `{}` is not a single character", layer, row, token),
+            LayoutError::TooManyLayers(n) =>
+                write!(f, "found {} unnamed layers, expected a base and an optional shifted layer", n),
+            LayoutError::UnnamedLayer(layer) =>
+                write!(f, "layer {} follows a named layer but has no `@name` line", layer),
+            LayoutError::DuplicateLayer(name) => write!(f, "layer `{}` is given twice", name),
+            LayoutError::UnknownLayer(name) => write!(f, "`@{}` reaches a layer that isn't given", name),
+            LayoutError::NoAccessKey(name) => write!(f, "no key on the base layer reaches layer `{}`", name),
+            LayoutError::EmptyKey(i) => write!(f, "position {} of the base layer is empty", i),
+            LayoutError::UnknownShift(c) =>
+                write!(f, "no shifted layer given and `{}` has no default shifted character", c),
+            LayoutError::Invalid(e) => write!(f, "invalid layout: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl From<io::Error> for LayoutError {
+    fn from(e: io::Error) -> Self { LayoutError::Io(e) }
+}
+
+/// How a position with nothing on it is written.
+const EMPTY: &str = "---";
+
+/// How the `#` key is written at the start of a row, where it would make the
+/// row a comment.
+const ESCAPED_HASH: &str = "\\#";
+
+/// Keys written by name in layout files.
+const KEY_NAMES: [(char, &str); 4] = [(' ', "spc"), ('\n', "ent"), ('\t', "tab"), ('\u{8}', "bsp")];
+
+/// The name `c` is written as in layout files, if it has one.
+fn key_name(c: char) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|&&(k, _)| k == c).map(|&(_, name)| name)
+}
+
+fn is_special(c: char) -> bool {
+    key_name(c).is_some()
+}
+
+/// Shifted character of `c` on a US keyboard.
+fn default_shift(c: char) -> Option<char> {
+    if c.is_ascii_alphabetic() { return Some(c.to_ascii_uppercase()) }
+    if is_special(c) { return Some(c) }
+    let shift = match c {
+        '`' => '~', '1' => '!', '2' => '@', '3' => '#', '4' => '$', '5' => '%',
+        '6' => '^', '7' => '&', '8' => '*', '9' => '(', '0' => ')', '-' => '_',
+        '=' => '+', '[' => '{', ']' => '}', '\\' => '|', ';' => ':', '\'' => '"',
+        ',' => '<', '.' => '>', '/' => '?',
+        _ => return None,
+    };
+    Some(shift)
+}
+
+fn make_key(base: char, shift: char) -> Key {
+    match base {
+        c if is_special(c) => Key::Special(base, shift),
+        c if c.is_alphabetic() => Key::Letter(base, shift),
+        c if c.is_ascii_digit() => Key::Number(base, shift),
+        _ => Key::Punctuation(base, shift),
+    }
+}
+
+/// How `c` is written in a layout file.
+pub(crate) fn label(c: char) -> String {
+    key_name(c).map_or_else(|| c.to_string(), str::to_string)
+}
+
+/// The key a layout gets for `c` when it doesn't give one.
+pub(crate) fn default_key(c: char) -> Key {
+    make_key(c, default_shift(c).unwrap_or(c))
+}
+
+/// Default keys of every row from `row` on, if they all have them.
+pub(crate) fn default_rows(geometry: &Geometry, row: usize) -> Option<Vec<char>> {
+    (row..geometry.row_count())
+        .map(|r| geometry.row_defaults(r))
+        .collect::<Option<Vec<&[char]>>>()
+        .map(|rows| rows.concat())
+}
+
+/// One position of a grid.
+#[derive(Debug, Clone, PartialEq)]
+enum Slot {
+    Char(char),
+    /// `@name`: held to reach the named layer.
+    Access(String),
+    Empty,
+}
+
+fn parse_slot(token: &str) -> Option<Slot> {
+    if token == EMPTY { return Some(Slot::Empty) }
+    if token == ESCAPED_HASH { return Some(Slot::Char('#')) }
+    if let Some(name) = token.strip_prefix('@').filter(|name| !name.is_empty()) {
+        return Some(Slot::Access(name.to_string()))
+    }
+    if let Some(&(c, _)) = KEY_NAMES.iter().find(|&&(_, name)| name == token) {
+        return Some(Slot::Char(c))
+    }
+    let mut it = token.chars();
+    match (it.next(), it.next()) {
+        (Some(c), None) => Some(Slot::Char(c)),
+        _ => None,
+    }
+}
+
+/// Parses one grid. Rows left out get the geometry's default keys, or
+/// nothing on a `named` layer.
+fn parse_layer(layer: usize, rows: &[&str], geometry: &Geometry, named: bool) -> Result<Vec<Slot>, LayoutError> {
+    let defaults = match rows.len() <= geometry.row_count() {
+        true => default_rows(geometry, rows.len()),
+        false => None,
+    };
+    let Some(defaults) = defaults else {
+        return Err(LayoutError::RowCount { layer, expected: geometry.row_count(), found: rows.len() })
+    };
+
+    let mut slots = Vec::with_capacity(geometry.len());
+    for (row, line) in rows.iter().enumerate() {
+        let range = geometry.row_range(row);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() != range.len() {
+            return Err(LayoutError::KeyCount { layer, row, expected: range.len(), found: tokens.len() })
+        }
+        for token in tokens {
+            match parse_slot(token) {
+                Some(slot) => slots.push(slot),
+                None => return Err(LayoutError::BadKey { layer, row, token: token.to_string() }),
+            }
+        }
+    }
+    slots.extend(defaults.into_iter().map(|c| if named { Slot::Empty } else { Slot::Char(c) }));
+    Ok(slots)
+}
+
+/// The name of a grid headed by an `@name` line.
+fn layer_name(rows: &[&str]) -> Option<String> {
+    let mut tokens = rows.first()?.split_whitespace();
+    match (tokens.next()?.strip_prefix('@'), tokens.next()) {
+        (Some(name), None) if !name.is_empty() => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+impl FromStr for Keyboard {
+    type Err = LayoutError;
+
+    /// Parses a layout for the built-in 47 key geometry.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Keyboard::parse(s, Geometry::ansi_47())
+    }
+}
+
+impl Keyboard {
+    pub fn parse(s: &str, geometry: Arc<Geometry>) -> Result<Self, LayoutError> {
+        // split into layers on blank lines, ignoring comments
+        let mut layers: Vec<Vec<&str>> = vec![];
+        let mut current: Vec<&str> = vec![];
+        for line in s.lines().filter(|l| !l.trim_start().starts_with('#')) {
+            if line.trim().is_empty() {
+                if !current.is_empty() { layers.push(std::mem::take(&mut current)) }
+            } else {
+                current.push(line);
+            }
+        }
+        if !current.is_empty() { layers.push(current) }
+
+        // the base and shifted grids, then the named layers
+        let mut unnamed: Vec<&[&str]> = vec![];
+        let mut named: Vec<(String, &[&str])> = vec![];
+        for (i, rows) in layers.iter().enumerate() {
+            match layer_name(rows) {
+                Some(name) if named.iter().any(|(n, _)| *n == name) => return Err(LayoutError::DuplicateLayer(name)),
+                Some(name) => named.push((name, &rows[1..])),
+                None if !named.is_empty() => return Err(LayoutError::UnnamedLayer(i)),
+                None => unnamed.push(rows),
+            }
+        }
+        if unnamed.len() > 2 { return Err(LayoutError::TooManyLayers(unnamed.len())) }
+
+        let base = parse_layer(0, unnamed.first().copied().unwrap_or_default(), &geometry, false)?;
+        let shifted = match unnamed.get(1) {
+            Some(rows) => Some(parse_layer(1, rows, &geometry, false)?),
+            None => None,
+        };
+
+        let mut keys = Vec::with_capacity(base.len());
+        for (i, slot) in base.into_iter().enumerate() {
+            keys.push(match slot {
+                Slot::Char(c) => {
+                    let shift = match shifted.as_ref().map(|s| &s[i]) {
+                        Some(Slot::Char(s)) => *s,
+                        Some(_) => c,
+                        None => default_shift(c).ok_or(LayoutError::UnknownShift(c))?,
+                    };
+                    make_key(c, shift)
+                },
+                Slot::Access(name) => match named.iter().position(|(n, _)| *n == name) {
+                    Some(layer) => Key::Layer(layer),
+                    None => return Err(LayoutError::UnknownLayer(name)),
+                },
+                Slot::Empty => return Err(LayoutError::EmptyKey(i)),
+            });
+        }
+
+        let mut extra = Vec::with_capacity(named.len());
+        for (layer, (name, rows)) in named.into_iter().enumerate() {
+            if !keys.contains(&Key::Layer(layer)) { return Err(LayoutError::NoAccessKey(name)) }
+            let slots = parse_layer(unnamed.len() + layer, rows, &geometry, true)?;
+            let keys = slots
+                .into_iter()
+                .map(|slot| match slot {
+                    Slot::Char(c) => Some(c),
+                    _ => None,
+                })
+                .collect();
+            extra.push(Layer { name, keys });
+        }
+
+        let keyboard = Keyboard::with_geometry(geometry, keys)
+            .and_then(|keyboard| keyboard.with_layers(extra))
+            .expect("one key was parsed for every position");
+        keyboard.validate().map_err(LayoutError::Invalid)?;
+        Ok(keyboard)
+    }
+
+    pub fn from_file(path: &Path, geometry: Arc<Geometry>) -> Result<Self, LayoutError> {
+        Keyboard::parse(&fs::read_to_string(path)?, geometry)
+    }
+
+    /// The layout file form of this keyboard: the base grid, a blank line,
+    /// the shifted grid, then every other layer.
+    pub fn to_layout_string(&self) -> String {
+        let row_start = |i: usize| self.geometry.row_of(i).map(|row| self.geometry.row_range(row).start) == Some(i);
+        let grid = |chars: &dyn Fn(usize) -> Option<char>| self.grid(|i| match (self.keys[i], chars(i)) {
+            (Key::Layer(layer), _) => self.access_label(layer),
+            (_, Some('#')) if row_start(i) => ESCAPED_HASH.to_string(),
+            (_, c) => c.map_or_else(|| EMPTY.to_string(), label),
+        });
+
+        let mut text = format!(
+            "{}\n\n{}\n",
+            grid(&|i| self.keys[i].chars().map(|(base, _)| base)),
+            grid(&|i| self.keys[i].chars().map(|(_, shift)| shift)),
+        );
+        for layer in &self.layers {
+            text.push_str(&format!("\n@{}\n{}\n", layer.name, grid(&|i| layer.keys[i])));
+        }
+        text
+    }
+}
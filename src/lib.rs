@@ -1,81 +1,157 @@
 use std::{
-    io::{self, Read},
-    fs::{File, self},
-    path::PathBuf, env, cmp::Ordering, sync::atomic::AtomicU32,
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt,
+    hash::{Hash, Hasher},
+    path::Path,
+    sync::{Arc, Mutex, OnceLock},
 };
 
 use rayon::prelude::*;
-use rand::Rng;
+use rand::{seq::SliceRandom, Rng};
+use rand_chacha::ChaCha8Rng;
+
+mod checkpoint;
+mod config;
+mod constraint;
+mod corpus;
+mod diff;
+mod geometry;
+mod layer;
+mod layout;
+mod metric;
+mod preset;
+mod stats;
+mod validate;
+
+pub use checkpoint::{Checkpoint, CheckpointError, GroupState, RngState};
+pub use config::{ConfigError, GaConfig, MoveConfig};
+pub use constraint::Constraint;
+pub use corpus::Corpus;
+pub use diff::{LayoutDiff, Place};
+pub use geometry::{Finger, Fingering, Geometry, GeometryError, Hand};
+pub use layer::Layer;
+pub use layout::LayoutError;
+pub use metric::{
+    Effort, FingerLoad, FingerWeights, LayerSwitch, Learnability, LearnabilityWeights, Metric, SameFingerBigrams, ScoreModel,
+    ScoreWeights, Shift, Travel, TrigramFlow,
+};
+pub use stats::{LayoutStats, Trigram};
+pub use validate::KeyboardError;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Keyboard {
-    keys: [Key; 47],
-    pub heatmap: [f32; 47],
-    hands: [Finger; 8],
-}
-
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-enum Finger {
-    // row 0 (top row) through row 3 (bottom)
-    LPinky([usize; 2], usize, usize, usize),
-    LRing(usize, usize, usize, usize),
-    LMid(usize, usize, usize, usize),
-    LIndex([usize; 2], [usize; 2], [usize; 2], [usize; 2]),
-
-    RIndex([usize; 2], [usize; 2], [usize; 2], [usize; 2]),
-    RMid(usize, usize, usize, usize),
-    RRing(usize, usize, usize, usize),
-    RPinky([usize; 3], [usize; 4], [usize; 2], usize),
+    keys: Vec<Key>,
+    layers: Vec<Layer>,
+    geometry: Arc<Geometry>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Key {
     Letter(char, char),
-    StaticLetter(char, char),
     Number(char, char),
     Punctuation(char, char),
+    /// Space, enter, tab or backspace.
+    Special(char, char),
+    /// Held to reach the keyboard's layer with this index.
+    Layer(usize),
 }
 
 impl Key {
     pub fn match_char(&self, c: char) -> bool {
         match self {
-            Key::Letter(k1, k2) =>       if *k1 == c || *k2 == c { true } else { false },
-            Key::StaticLetter(k1, k2) => if *k1 == c || *k2 == c { true } else { false },
-            Key::Number(k1, k2) =>       if *k1 == c || *k2 == c { true } else { false },
-            Key::Punctuation(k1, k2) =>  if *k1 == c || *k2 == c { true } else { false },
+            Key::Letter(k1, k2) =>       *k1 == c || *k2 == c,
+            Key::Number(k1, k2) =>       *k1 == c || *k2 == c,
+            Key::Punctuation(k1, k2) =>  *k1 == c || *k2 == c,
+            Key::Special(k1, k2) =>      *k1 == c || *k2 == c,
+            Key::Layer(_) =>             false,
+        }
+    }
+
+    /// The base and shifted characters; layer keys have none.
+    pub fn chars(&self) -> Option<(char, char)> {
+        match *self {
+            Key::Letter(base, shift)
+            | Key::Number(base, shift)
+            | Key::Punctuation(base, shift)
+            | Key::Special(base, shift) => Some((base, shift)),
+            Key::Layer(_) => None,
         }
     }
+
     pub fn key_val(&self) -> String {
         match self {
             Key::Letter(x, _) => x.to_string(),
-            Key::StaticLetter(x, _) => x.to_string(),
             Key::Punctuation(x, _) => x.to_string(),
             Key::Number(x, _) => x.to_string(),
+            Key::Special(x, _) => layout::label(*x),
+            Key::Layer(layer) => format!("@{}", layer),
         }
     }
 }
 
+impl fmt::Display for Keyboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.grid(|i| self.key_label(i)))
+    }
+}
+
+/// Keyboards are equal when they hold the same keys and layers in the same
+/// places, whatever their geometry.
 impl PartialEq for Keyboard {
     fn eq(&self, other: &Self) -> bool {
-        for (i, k) in self.keys.iter().enumerate() {
-            if *k != other.keys[i] { return false }
-        }
-        true
+        self.keys == other.keys && self.layers == other.layers
     }
+}
 
-    fn ne(&self, other: &Self) -> bool {
-        for (i, k) in self.keys.iter().enumerate() {
-            if *k == other.keys[i] { return false }
-        }
-        true
-        
+impl Eq for Keyboard {}
+
+impl Hash for Keyboard {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.keys.hash(state);
+        self.layers.hash(state);
     }
 }
 
 impl Keyboard {
-    pub fn new_random() -> Self {
-        let mut available_spots = vec![15, 16, 19, 20, 21, 22, 23, 24, 28, 30,
-                                      35, 36, 39, 40, 41, 42, 43, 44, 45];
+    /// A keyboard on the built-in 47 key geometry. The thumb row below the
+    /// 47 keys gets its usual keys.
+    pub fn from_keys(keys: [Key; 47]) -> Self {
+        let geometry = Geometry::ansi_47();
+        let thumbs = layout::default_rows(&geometry, 4).expect("the built-in thumb row has defaults");
+        let keys = keys.into_iter().chain(thumbs.into_iter().map(layout::default_key)).collect();
+        Self { keys, layers: vec![], geometry }
+    }
+
+    /// A keyboard on any geometry. `keys` are listed row by row and must
+    /// fill every position of the geometry.
+    pub fn with_geometry(geometry: Arc<Geometry>, keys: Vec<Key>) -> Option<Self> {
+        (keys.len() == geometry.len()).then_some(Self { keys, layers: vec![], geometry })
+    }
+
+    /// This keyboard on another geometry with the same number of keys.
+    pub(crate) fn on(&self, geometry: Arc<Geometry>) -> Keyboard {
+        assert_eq!(self.keys.len(), geometry.len(), "keyboards only move to geometries of the same size");
+        Keyboard { geometry, ..self.clone() }
+    }
+
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    pub fn geometry(&self) -> &Arc<Geometry> {
+        &self.geometry
+    }
+
+    /// Hash of the key arrangement, the same for every equal keyboard.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn new_random(rng: &mut impl Rng) -> Self {
+        let mut available_spots = Geometry::ansi_47().moves().movable.clone()
+            .expect("the built-in geometry lists its movable keys");
         let mut available_keys = vec![
             Key::Letter('i', 'I'),
             Key::Letter('o', 'O'),
@@ -129,21 +205,18 @@ impl Keyboard {
         keys[26] = Some(Key::Letter('a', 'A'));
         keys[27] = Some(Key::Letter('s', 'S'));
         keys[29] = Some(Key::Letter('e', 'E'));
-        keys[31] = Some(Key::StaticLetter('h', 'H'));
-        keys[32] = Some(Key::StaticLetter('j', 'J'));
-        keys[33] = Some(Key::StaticLetter('k', 'K'));
-        keys[34] = Some(Key::StaticLetter('l', 'L'));
+        keys[31] = Some(Key::Letter('h', 'H'));
+        keys[32] = Some(Key::Letter('j', 'J'));
+        keys[33] = Some(Key::Letter('k', 'K'));
+        keys[34] = Some(Key::Letter('l', 'L'));
 
 
-        keys[37] = Some(Key::Punctuation(';', ';'));
+        keys[37] = Some(Key::Punctuation(';', ':'));
         keys[38] = Some(Key::Punctuation('\'', '"'));
         keys[46] = Some(Key::Punctuation('/', '?'));
 
-        available_spots.sort_by(|_, _|
-            if rand::thread_rng().gen_bool(0.5) { Ordering::Greater } else { Ordering::Less });
-
-        available_keys.sort_by(|_, _|
-            if rand::thread_rng().gen_bool(0.5) { Ordering::Greater } else { Ordering::Less });
+        available_spots.shuffle(rng);
+        available_keys.shuffle(rng);
 
         for &spot in available_spots.iter() {
             keys[spot] = available_keys.pop();
@@ -155,183 +228,186 @@ impl Keyboard {
             .collect();
 
 
-        use Finger as F;
-        Self {
-            keys: key_vec.try_into().unwrap(),
-            heatmap: [
-3.,     2.,     2.,     2.,     2.,     3.,     3.,     2.,     2.,     2.,     2.,     3.,     4.,
-
-            1.25,   1.5,    0.75,   0.75,   2.,     2.5,    0.75,   0.75,   1.,     1.,     3.,     3.5,    4.,
-
-              0.25,   0.5,    0.,     0.,     1.,     1.,     0.,     0.,     0.5,    0.25,   1.,
-
-                  1.25,   1.5,    1.,     1.,     2.,     1.,     1.,     1.,     1.5,    1.25,
-            ],
-            hands: [
-                F::LPinky(
-                    [0, 1],
-                    13,
-                    26,
-                    37,
-                ),
-                F::LRing(
-                    2,
-                    14,
-                    27,
-                    38,
-                ),
-                F::LMid(
-                    3,
-                    15,
-                    28,
-                    39,
-                ),
-                F::LIndex(
-                    [4, 5],
-                    [16, 17],
-                    [29, 30],
-                    [40, 41],
-                ),
-
-                F::RIndex(
-                    [6, 7],
-                    [18, 19],
-                    [31, 32],
-                    [42, 43]
-                ),
-                F::RMid(
-                    8,
-                    20,
-                    33,
-                    44,
-                ),
-                F::RRing(
-                    9,
-                    21,
-                    34,
-                    45,
-                ),
-                F::RPinky(
-                    [10, 11, 12],
-                    [22, 23, 24, 25],
-                    [35, 36],
-                    46,
-                ),
-            ] 
-        }
+        Self::from_keys(key_vec.try_into().unwrap())
+    }
+
+    /// Draws the keys where they sit on the board, four columns per key width:
+    ///
+    /// ```text
+    /// `   1   2   3   4   5   6   7   8   9   0   -   =
+    ///       q   w   e   r   t   y   u   i   o   p   [   ]   \
+    ///        a   s   d   f   g   h   j   k   l   ;   '
+    ///          z   x   c   v   b   n   m   ,   .   /
+    /// ```
+    fn grid(&self, label: impl Fn(usize) -> String) -> String {
+        self.styled_grid(label, |_, label| label)
+    }
+
+    /// `grid` with every label passed through `style`, which may add colour
+    /// without moving the keys.
+    fn styled_grid(&self, label: impl Fn(usize) -> String, style: impl Fn(usize, String) -> String) -> String {
+        (0..self.geometry.row_count())
+            .map(|row| {
+                let (mut line, mut width) = (String::new(), 0);
+                for i in self.geometry.row_range(row) {
+                    let column = (self.geometry.x_of(i).unwrap() * 4.).round() as usize;
+                    let padding = column.saturating_sub(width).max(usize::from(width > 0));
+                    let label = label(i);
+                    width += padding + label.chars().count();
+                    line.push_str(&" ".repeat(padding));
+                    line.push_str(&style(i, label));
+                }
+                line
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
     }
 
     pub fn print_self(&self) {
-        // `   1   2   3   4   5   6   7   8   9   0   -   =
-        //       q   w   e   r   t   y   u   i   o   p   [   ]   \
-        //        a   s   d   f   g   h   j   k   l   ;   '
-        //          z   x   c   v   b   n   m   ,   .   /
-        println!("{}   {}   {}   {}   {}   {}   {}   {}   {}   {}   {}   {}   {}",
-            self.keys[0].key_val(),
-            self.keys[1].key_val(),
-            self.keys[2].key_val(),
-            self.keys[3].key_val(),
-            self.keys[4].key_val(),
-            self.keys[5].key_val(),
-            self.keys[6].key_val(),
-            self.keys[7].key_val(),
-            self.keys[8].key_val(),
-            self.keys[9].key_val(),
-            self.keys[10].key_val(),
-            self.keys[11].key_val(),
-            self.keys[12].key_val(),
-        );
-        println!("      {}   {}   {}   {}   {}   {}   {}   {}   {}   {}   {}   {}   {}",
-            self.keys[13].key_val(),
-            self.keys[14].key_val(),
-            self.keys[15].key_val(),
-            self.keys[16].key_val(),
-            self.keys[17].key_val(),
-            self.keys[18].key_val(),
-            self.keys[19].key_val(),
-            self.keys[20].key_val(),
-            self.keys[21].key_val(),
-            self.keys[22].key_val(),
-            self.keys[23].key_val(),
-            self.keys[24].key_val(),
-            self.keys[25].key_val(),
-        );
-        println!("       {}   {}   {}   {}   {}   {}   {}   {}   {}   {}   {}",
-            self.keys[26].key_val(),
-            self.keys[27].key_val(),
-            self.keys[28].key_val(),
-            self.keys[29].key_val(),
-            self.keys[30].key_val(),
-            self.keys[31].key_val(),
-            self.keys[32].key_val(),
-            self.keys[33].key_val(),
-            self.keys[34].key_val(),
-            self.keys[35].key_val(),
-            self.keys[36].key_val(),
-        );
-        println!("         {}   {}   {}   {}   {}   {}   {}   {}   {}   {}",
-            self.keys[37].key_val(),
-            self.keys[38].key_val(),
-            self.keys[39].key_val(),
-            self.keys[40].key_val(),
-            self.keys[41].key_val(),
-            self.keys[42].key_val(),
-            self.keys[43].key_val(),
-            self.keys[44].key_val(),
-            self.keys[45].key_val(),
-            self.keys[46].key_val(),
-        );
+        println!("{}", self);
+    }
+
+    /// Positions the optimizer may swap keys between.
+    fn movable(&self) -> Vec<usize> {
+        let moves = self.geometry.moves();
+        let mut movable: Vec<usize> = match &moves.movable {
+            Some(movable) => movable.iter().copied().filter(|i| !moves.pinned.values().any(|p| p == i)).collect(),
+            None => self.unpinned().filter(|&i| matches!(self.keys[i], Key::Letter(_, _))).collect(),
+        };
+        let thumbs = self.thumb_keys();
+        if thumbs.len() > 1 { movable.extend(thumbs) }
+        movable
+    }
+
+    fn unpinned(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.keys.len()).filter(|i| !self.geometry.moves().pinned.values().any(|p| p == i))
+    }
+
+    /// Positions of the special keys on thumbs, which may swap among
+    /// themselves.
+    fn thumb_keys(&self) -> Vec<usize> {
+        self.unpinned()
+            .filter(|&i| matches!(self.keys[i], Key::Special(_, _)))
+            .filter(|&i| self.geometry.finger_of(i).is_some_and(Finger::is_thumb))
+            .collect()
     }
-    pub fn reproduce(&self, mutations: usize) -> Keyboard {
+
+    /// This keyboard with its movable letters, its thumb keys and the
+    /// characters of each layer in a random order.
+    pub fn shuffled(&self, rng: &mut impl Rng) -> Keyboard {
         let mut new_keyboard = self.clone();
+        let spots: Vec<usize> = self.movable()
+            .into_iter()
+            .filter(|&k| matches!(self.keys[k], Key::Letter(_, _)))
+            .collect();
+        for spots in [spots, self.thumb_keys()] {
+            let mut keys: Vec<Key> = spots.iter().map(|&k| self.keys[k]).collect();
+            keys.shuffle(rng);
 
-        let available_keys = [15, 16, 19, 20, 21, 22, 23, 24, 28, 30, 35, 36, 39, 40, 41, 42, 43, 44, 45];
+            for (spot, key) in spots.into_iter().zip(keys) {
+                new_keyboard.keys[spot] = key;
+            }
+        }
+        for (layer, spots) in self.layer_slots().into_iter().enumerate() {
+            let mut chars: Vec<Option<char>> = spots.iter().map(|&k| self.layers[layer].keys[k]).collect();
+            chars.shuffle(rng);
 
-        let letter_only_keys = [19, 20];
+            for (spot, c) in spots.into_iter().zip(chars) {
+                new_keyboard.layers[layer].keys[spot] = c;
+            }
+        }
+        new_keyboard
+    }
 
-        let non_letter_only_keys = [21, 22, 23, 24, 36, 45];
+    pub fn reproduce(&self, mutations: usize, rng: &mut impl Rng) -> Keyboard {
+        let mut new_keyboard = self.clone();
+
+        let available_keys = self.movable();
+        let moves = self.geometry.moves();
+        let letter_only_keys = moves.letters_only();
+        // positions on layers, which swap within their layer or, for symbols,
+        // with a character of a movable punctuation key
+        let layer_slots = self.layer_slots();
+        let layer_picks: Vec<(usize, usize)> = layer_slots
+            .iter()
+            .enumerate()
+            .flat_map(|(layer, spots)| spots.iter().map(move |&spot| (layer, spot)))
+            .collect();
+        if available_keys.is_empty() && layer_picks.is_empty() { return new_keyboard }
 
         let letter_keys: Vec<usize> = available_keys
-            .into_iter()
-            .filter(|&k| {
-                match new_keyboard.keys[k] {
-                    Key::Letter(_, _) => true,
-                    _ => false,
-                }
-            })
+            .iter()
+            .copied()
+            .filter(|&k| matches!(new_keyboard.keys[k], Key::Letter(_, _)))
             .collect();
 
         let punc_keys: Vec<usize> = available_keys
-            .into_iter()
-            .filter(|&k| {
-                match new_keyboard.keys[k] {
-                    Key::Punctuation(_, _) => true,
-                    _ => false,
-                }
-            })
+            .iter()
+            .copied()
+            .filter(|&k| matches!(new_keyboard.keys[k], Key::Punctuation(_, _)))
+            .collect();
+
+        let thumb_keys = self.thumb_keys();
+        let others: Vec<usize> = available_keys
+            .iter()
+            .copied()
+            .filter(|k| !thumb_keys.contains(k))
             .collect();
 
+        // punctuation moves anywhere when the geometry doesn't say where, or
+        // when none of the places it names are movable
+        let non_letter_only_keys: Vec<usize> = moves.punctuation
+            .iter()
+            .copied()
+            .filter(|k| available_keys.contains(k))
+            .collect();
+        let non_letter_only_keys = match non_letter_only_keys.is_empty() {
+            true => others.clone(),
+            false => non_letter_only_keys,
+        };
+
         for _ in 0..mutations {
-            let rand_key_index = available_keys[rand::thread_rng().gen_range(0..available_keys.len())];
-            let rand_key_punc = punc_keys
-                .clone()
-                .into_iter()
-                .find(|i| *i == rand_key_index)
-                .is_some();
-            let rand_key_letter_only = letter_only_keys
-                .clone()
-                .into_iter()
-                .find(|i| *i == rand_key_index)
-                .is_some();
+            let pick = rng.gen_range(0..available_keys.len() + layer_picks.len());
+            if let Some(&(layer, spot)) = pick.checked_sub(available_keys.len()).and_then(|i| layer_picks.get(i)) {
+                let spots = &layer_slots[layer];
+                let symbol = new_keyboard.layers[layer].keys[spot]
+                    .filter(|&c| matches!(layout::default_key(c), Key::Punctuation(_, _)));
+                let crossings = if symbol.is_some() { punc_keys.len() * 2 } else { 0 };
+                let other = rng.gen_range(0..spots.len() + crossings);
+                let Some(crossing) = other.checked_sub(spots.len()) else {
+                    new_keyboard.layers[layer].keys.swap(spot, spots[other]);
+                    continue
+                };
+
+                let (position, shifted) = (punc_keys[crossing / 2], crossing % 2 == 1);
+                let (Some(symbol), Key::Punctuation(base, shift)) = (symbol, new_keyboard.keys[position]) else { continue };
+                let old_key = new_keyboard.keys[position];
+                let (key, taken) = match shifted {
+                    false => (Key::Punctuation(symbol, shift), base),
+                    true => (Key::Punctuation(base, symbol), shift),
+                };
+                new_keyboard.keys[position] = key;
+                new_keyboard.layers[layer].keys[spot] = Some(taken);
+                if !new_keyboard.meets_constraints() {
+                    new_keyboard.keys[position] = old_key;
+                    new_keyboard.layers[layer].keys[spot] = Some(symbol);
+                }
+                continue
+            }
+
+            let rand_key_index = available_keys[pick];
+            let rand_key_punc = punc_keys.contains(&rand_key_index);
+            let rand_key_letter_only = letter_only_keys.contains(&rand_key_index);
 
             let other_key_index = match (rand_key_letter_only, rand_key_punc) {
-                (true, _)      => letter_keys[rand::thread_rng().gen_range(0..letter_keys.len())],
-                (false, true)  => non_letter_only_keys[rand::thread_rng().gen_range(0..non_letter_only_keys.len())],
-                (false, false) => available_keys[rand::thread_rng().gen_range(0..available_keys.len())],
+                _ if thumb_keys.contains(&rand_key_index) => thumb_keys[rng.gen_range(0..thumb_keys.len())],
+                (true, _)      => letter_keys[rng.gen_range(0..letter_keys.len())],
+                (false, true)  => non_letter_only_keys[rng.gen_range(0..non_letter_only_keys.len())],
+                (false, false) => others[rng.gen_range(0..others.len())],
             };
 
-            let key1 = new_keyboard.keys[rand_key_index].clone();
-            let key2 = new_keyboard.keys[other_key_index].clone();
+            let key1 = new_keyboard.keys[rand_key_index];
+            let key2 = new_keyboard.keys[other_key_index];
 
             {
                 let key1_ref = new_keyboard.keys.get_mut(rand_key_index).unwrap();
@@ -341,127 +417,69 @@ impl Keyboard {
                 let key2_ref = new_keyboard.keys.get_mut(other_key_index).unwrap();
                 *key2_ref = key1;
             }
+
+            // swaps breaking a constraint are undone
+            if !new_keyboard.meets_constraints() {
+                new_keyboard.keys.swap(rand_key_index, other_key_index);
+            }
         }
 
         new_keyboard
     }
 
     pub fn new_47() -> Self {
-        use Finger as F;
-        Self {
-            keys: [
-                Key::Punctuation('`', '~'),
-                Key::Number('1', '!'),
-                Key::Number('2', '@'),
-                Key::Number('3', '#'),
-                Key::Number('4', '$'),
-                Key::Number('5', '%'),
-                Key::Number('6', '^'),
-                Key::Number('7', '&'),
-                Key::Number('8', '*'),
-                Key::Number('9', '('),
-                Key::Number('0', ')'),
-                Key::Punctuation(',', '<'),
-                Key::Punctuation('.', '>'),
-
-                Key::Punctuation('[', '{'),
-                Key::Punctuation(']', '}'),
-                Key::Letter('i', 'I'),
-                Key::Letter('o', 'O'),
-                Key::Punctuation('-', '_'),
-                Key::Punctuation('=', '+'),
-                Key::Letter('f', 'F'),
-                Key::Letter('n', 'N'),
-                Key::Letter('w', 'W'),
-                Key::Letter('v', 'V'),
-                Key::Letter('q', 'Q'),
-                Key::Letter('z', 'Z'),
-                Key::Punctuation('\\', '|'),
-
-                Key::Letter('a', 'A'),
-                Key::Letter('r', 'R'),
-                Key::Letter('t', 'T'),
-                Key::Letter('e', 'E'),
-                Key::Letter('c', 'c'),
-                Key::StaticLetter('h', 'H'),
-                Key::StaticLetter('j', 'J'),
-                Key::StaticLetter('k', 'K'),
-                Key::StaticLetter('l', 'L'),
-                Key::Letter('s', 'S'),
-                Key::Letter('g', 'G'),
+        Self::from_keys([
+            Key::Punctuation('`', '~'),
+            Key::Number('1', '!'),
+            Key::Number('2', '@'),
+            Key::Number('3', '#'),
+            Key::Number('4', '$'),
+            Key::Number('5', '%'),
+            Key::Number('6', '^'),
+            Key::Number('7', '&'),
+            Key::Number('8', '*'),
+            Key::Number('9', '('),
+            Key::Number('0', ')'),
+            Key::Punctuation(',', '<'),
+            Key::Punctuation('.', '>'),
+
+            Key::Punctuation('[', '{'),
+            Key::Punctuation(']', '}'),
+            Key::Letter('i', 'I'),
+            Key::Letter('o', 'O'),
+            Key::Punctuation('-', '_'),
+            Key::Punctuation('=', '+'),
+            Key::Letter('f', 'F'),
+            Key::Letter('n', 'N'),
+            Key::Letter('w', 'W'),
+            Key::Letter('v', 'V'),
+            Key::Letter('q', 'Q'),
+            Key::Letter('z', 'Z'),
+            Key::Punctuation('\\', '|'),
+
+            Key::Letter('a', 'A'),
+            Key::Letter('r', 'R'),
+            Key::Letter('t', 'T'),
+            Key::Letter('e', 'E'),
+            Key::Letter('c', 'C'),
+            Key::Letter('h', 'H'),
+            Key::Letter('j', 'J'),
+            Key::Letter('k', 'K'),
+            Key::Letter('l', 'L'),
+            Key::Letter('s', 'S'),
+            Key::Letter('g', 'G'),
                 
-                Key::Punctuation(';', ':'),
-                Key::Punctuation('\'', '"'),
-                Key::Letter('b', 'B'),
-                Key::Letter('m', 'M'),
-                Key::Letter('x', 'X'),
-                Key::Letter('u', 'U'),
-                Key::Letter('d', 'D'),
-                Key::Letter('p', 'P'),
-                Key::Letter('y', 'Y'),
-                Key::Punctuation('/', '?'),
-            ],
-            heatmap: [
-3.,     2.,     2.,     2.,     2.,     3.,     3.,     2.,     2.,     2.,     2.,     3.,     4.,
-
-            1.25,   1.5,    0.75,   0.75,   2.,     2.5,    0.75,   0.75,   1.,     1.,     3.,     3.5,    4.,
-
-              0.25,   0.5,    0.,     0.,     1.,     1.,     0.,     0.,     0.5,    0.25,   1.,
-
-                  1.25,   1.5,    1.,     1.,     2.,     1.,     1.,     1.,     1.5,    1.25,
-            ],
-            hands: [
-                F::LPinky(
-                    [0, 1],
-                    13,
-                    26,
-                    37,
-                ),
-                F::LRing(
-                    2,
-                    14,
-                    27,
-                    38,
-                ),
-                F::LMid(
-                    3,
-                    15,
-                    28,
-                    39,
-                ),
-                F::LIndex(
-                    [4, 5],
-                    [16, 17],
-                    [29, 30],
-                    [40, 41],
-                ),
-
-                F::RIndex(
-                    [6, 7],
-                    [18, 19],
-                    [31, 32],
-                    [42, 43]
-                ),
-                F::RMid(
-                    8,
-                    20,
-                    33,
-                    44,
-                ),
-                F::RRing(
-                    9,
-                    21,
-                    34,
-                    45,
-                ),
-                F::RPinky(
-                    [10, 11, 12],
-                    [22, 23, 24, 25],
-                    [35, 36],
-                    46,
-                ),
-            ] 
-        }
+            Key::Punctuation(';', ':'),
+            Key::Punctuation('\'', '"'),
+            Key::Letter('b', 'B'),
+            Key::Letter('m', 'M'),
+            Key::Letter('x', 'X'),
+            Key::Letter('u', 'U'),
+            Key::Letter('d', 'D'),
+            Key::Letter('p', 'P'),
+            Key::Letter('y', 'Y'),
+            Key::Punctuation('/', '?'),
+        ])
     }
 
     pub fn get_key(&self, c: char) -> Option<&Key> {
@@ -475,12 +493,9 @@ impl Keyboard {
         rv
     }
 
+    /// The listed row of a key, as `Geometry::row_of`.
     pub fn index_to_row(&self, index: usize) -> Option<usize> {
-        if index < 13 { Some(0) }
-        else if index >= 13 && index < 26 { Some(1) }
-        else if index >= 26 && index < 37 { Some(2) }
-        else if index >= 37 && index < 47 { Some(3) }
-        else { None }
+        self.geometry.row_of(index)
     }
 
     pub fn distance(&self, a: usize, b: usize) -> f32 {
@@ -489,298 +504,191 @@ impl Keyboard {
         let a_hand = self.which_hand(a).expect("a_hand error");
         let b_hand = self.which_hand(b).expect("b_hand error");
 
-        let a_row = self.index_to_row(a).expect("a_row error");
-        let b_row = self.index_to_row(b).expect("b_row error");
+        let (_, a_y) = self.geometry.position(a).expect("a_y error");
+        let (_, b_y) = self.geometry.position(b).expect("b_y error");
 
-        let row_diff = b_row as i8 - a_row as i8;
+        // column stagger doesn't count as changing rows
+        let row_diff = b_y - a_y;
 
+        let heat = self.geometry.heatmap()[b];
         if a_hand == b_hand {
-            if row_diff == 0 { self.heatmap[b] as f32 * 0.75}
-            else if row_diff < 0 { self.heatmap[b] as f32 * 1.5 }
-            else { self.heatmap[b] as f32 }
+            if row_diff.abs() < 0.75 { heat * 0.75}
+            else if row_diff < 0. { heat * 1.5 }
+            else { heat }
         } else {
-            self.heatmap[b] as f32 * 1.25
+            heat * 1.25
         }
     }
 
+    /// Score of the keyboard under the default [`ScoreModel`].
+    pub fn score(&self, corpus: &Corpus) -> f32 {
+        ScoreModel::default().score(self, corpus)
+    }
+
+    /// `char_to_index` with the ASCII characters looked up ahead of time, for
+    /// loops over a whole corpus.
+    pub(crate) fn char_index(&self) -> impl Fn(char) -> Option<usize> + '_ {
+        let mut ascii = [None; 128];
+        for (c, slot) in ascii.iter_mut().enumerate() {
+            *slot = self.char_to_index(c as u8 as char);
+        }
+        move |c| if c.is_ascii() { ascii[c as usize] } else { self.char_to_index(c) }
+    }
+
+    /// Position of the key typing `c`, on the base or shifted layer first,
+    /// then on the other layers.
     pub fn char_to_index(&self, c: char) -> Option<usize> {
         for (i, k) in self.keys.iter().enumerate() {
             if k.match_char(c) {
-                return Some(i.try_into().unwrap());
+                return Some(i);
             }
         }
-        None
+        self.layers.iter().find_map(|layer| layer.keys.iter().position(|&k| k == Some(c)))
     }
 
-    pub fn which_hand(&self, index: usize) -> Option<Hand> {
-        let mut i = 0;
-        loop {
-            let hand = if i < 4 { Hand::Left } else { Hand::Right };
-            if let Some(finger) = self.hands.get(i) {
-                if finger.is_inside(index).is_some() { break Some(hand) }
-                i += 1;
-            } else {
-                break None
-            }
-        }
+    pub fn which_finger(&self, index: usize) -> Option<Finger> {
+        self.geometry.finger_of(index)
     }
-}
-
-#[derive(PartialEq, Eq)]
-pub enum Hand {
-    Left,
-    Right,
-}
 
-impl Finger {
-    pub fn is_inside(&self, key: usize) -> Option<usize> {
-        // returns the row if true
-        match self {
-            Self::LPinky(zero, one, two, three) => {
-                let mut rv = None;
-                for k in zero {
-                    if *k == key { 
-                        rv = Some(0)
-                    }
-                }
-                if key == *one { rv = Some(1) }
-                else if key == *two { rv = Some(2) }
-                else if key == *three { rv = Some(3) }
-                rv
-            },
-            Self::LRing(zero, one, two, three) => {
-                if key == *zero { Some(1) }
-                else if key == *one { Some(1) }
-                else if key == *two { Some(2) }
-                else if key == *three { Some(3) }
-                else { None }
-            },
-            Self::LMid(zero, one, two, three) => {
-                if key == *zero { Some(1) }
-                else if key == *one { Some(1) }
-                else if key == *two { Some(2) }
-                else if key == *three { Some(3) }
-                else { None }
-            },
-            Self::LIndex(zero, one, two, three) => {
-                let mut rv = None;
-                for k in zero {
-                    if *k == key { 
-                        rv = Some(0)
-                    }
-                }
-                for k in one {
-                    if *k == key { 
-                        rv = Some(1)
-                    }
-                }
-                for k in two {
-                    if *k == key { 
-                        rv = Some(2)
-                    }
-                }
-                for k in three {
-                    if *k == key { 
-                        rv = Some(3)
-                    }
-                }
-                rv
-            },
-
-            Self::RIndex(zero, one, two, three) => {
-                let mut rv = None;
-                for k in zero {
-                    if *k == key { 
-                        rv = Some(0)
-                    }
-                }
-                for k in one {
-                    if *k == key { 
-                        rv = Some(1)
-                    }
-                }
-                for k in two {
-                    if *k == key {
-                        rv = Some(2)
-                    }
-                }
-                for k in three {
-                    if *k == key { 
-                        rv = Some(3)
-                    }
-                }
-                rv
-            },
-            Self::RMid(zero, one, two, three) => {
-                if key == *zero { Some(1) }
-                else if key == *one { Some(1) }
-                else if key == *two { Some(2) }
-                else if key == *three { Some(3) }
-                else { None }
-            },
-            Self::RRing(zero, one, two, three) => {
-                if key == *zero { Some(1) }
-                else if key == *one { Some(1) }
-                else if key == *two { Some(2) }
-                else if key == *three { Some(3) }
-                else { None }
-            },
-            Self::RPinky(zero, one, two, three) => {
-                let mut rv = None;
-                for k in zero {
-                    if *k == key { 
-                        rv = Some(0)
-                    }
-                }
-                for k in one {
-                    if *k == key { 
-                        rv = Some(1)
-                    }
-                }
-                for k in two {
-                    if *k == key { 
-                        rv = Some(2)
-                    }
-                }
-                if key == *three { rv = Some(3) }
-                rv
-            },
-        }
+    pub fn which_hand(&self, index: usize) -> Option<Hand> {
+        self.geometry.hand_of(index)
     }
 }
 
-
-fn read_file(path: PathBuf) -> io::Result<String> {
-    let mut file = File::open(path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-
-    Ok(contents)
-}
-
-fn read_dir(path: PathBuf, keyboard: &Keyboard) -> io::Result<f32> {
-    let score = atomic_float::AtomicF32::new(0.);
-
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
-        let entry_path = entry.path();
-
-        if entry_path.is_file() {
-            if let Ok(contents) = read_file(entry_path) {
-                contents
-                    .chars()
-                    .collect::<Vec<char>>()
-                    .windows(2)
-                    .for_each(|c| {
-                        match (keyboard.char_to_index(c[0]), keyboard.char_to_index(c[1])) {
-                            (Some(a), Some(b)) => {
-                                score.fetch_add(keyboard.distance(a, b), std::sync::atomic::Ordering::Relaxed);
-                            },
-                            (None, Some(b)) => {
-                                score.fetch_add(keyboard.heatmap[b] as f32, std::sync::atomic::Ordering::Relaxed);
-                            },
-                            (_, None) => (),
-                        };
-                    }
-                )
-            }
-        } else if entry_path.is_dir() {
-            score.fetch_add(read_dir(entry_path, keyboard).unwrap(), std::sync::atomic::Ordering::Relaxed);
-        }
-    }
-
-    Ok(score.load(std::sync::atomic::Ordering::Relaxed))
+/// Runs the genetic search, minimizing the score `config.weights` gives.
+/// Every group starts from `start` if given, otherwise from a random layout.
+/// Fails if `config` is invalid or the run can't be set up with it.
+pub fn go(corpus: &Corpus, config: &GaConfig, start: Option<&Keyboard>) -> Result<Vec<(f32, Keyboard)>, ConfigError> {
+    config.validate()?;
+    let geometry = start.map_or_else(Geometry::ansi_47, |k| k.geometry().clone());
+    let checkpoint = Checkpoint::new(config, geometry, start)?;
+    let model = ScoreModel::for_geometry(&config.weights, &checkpoint.geometry)?;
+    Ok(resume(corpus, &model, checkpoint, None).expect("nothing is written without a checkpoint path"))
 }
 
-pub fn go() {
-    let mut path = env::current_dir()
-        .expect("lmao");
-    path.push("pile");
-
-    let group_num = AtomicU32::new(1);
-
-    let mut results: Vec<(f32, Keyboard)> = (0..100)
+/// Carries on the run saved in `checkpoint`, minimizing `model`'s score.
+/// Groups that already finished
+/// keep their result; the rest pick up exactly where they were saved, so a
+/// resumed run ends the same way an uninterrupted one would.
+///
+/// Scores are kept for the whole run, so a layout bred again, in any group,
+/// isn't scored twice.
+///
+/// With `save_to`, the checkpoint is written there every
+/// `checkpoint_interval` generations of each group and when a group finishes.
+pub fn resume(
+    corpus: &Corpus,
+    model: &ScoreModel,
+    checkpoint: Checkpoint,
+    save_to: Option<&Path>,
+) -> Result<Vec<(f32, Keyboard)>, CheckpointError> {
+    let GaConfig {
+        groups, population, survivors, ref mutation_schedule, stagnation_window, checkpoint_interval, ..
+    } = checkpoint.config.clone();
+    let start = checkpoint.start.clone();
+    let geometry = checkpoint.geometry.clone();
+    // random layouts shuffle the start, or the built-in layout's keys with
+    // the run's pins, over the run's movable positions
+    let template = match &checkpoint.start {
+        Some(start) => start.clone(),
+        None => checkpoint.config.moves.pin(&Keyboard::new_47().on(geometry.clone()))
+            .expect("pins were checked when the run was set up"),
+    };
+    let states = checkpoint.groups.clone();
+    let saver = save_to.map(|path| checkpoint::Saver::new(&checkpoint, path)).transpose()?;
+
+    // scores by keyboard, shared by every group so no layout is scored twice;
+    // a keyboard asked for again while being scored waits for its score
+    let scores: Mutex<HashMap<Keyboard, Arc<OnceLock<f32>>>> = Mutex::new(HashMap::new());
+    let score = |keyboard: &Keyboard| -> f32 {
+        let cell = scores.lock().unwrap().entry(keyboard.clone()).or_default().clone();
+        *cell.get_or_init(|| model.score(keyboard, corpus))
+    };
+
+    let save = |group: usize, state: &GroupState| -> Result<(), CheckpointError> {
+        saver.as_ref().map_or(Ok(()), |saver| saver.save(group, state))
+    };
+
+    let width = groups.to_string().len();
+
+    let mut results: Vec<(f32, Keyboard)> = states
         .into_par_iter()
-        .map(|_| {
-            let id = group_num.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-            println!("\n{} percent started\n", id);
-            let mut keyboards: Vec<Keyboard> = vec![Keyboard::new_random(); 100];
-            let mut top_50 = (0..50)
-                .into_par_iter()
-                .map(|_| {
-                    let k = Keyboard::new_random();
-                    (read_dir(path.clone(), &k).expect("you fucked up"), k)
-                })
-                .collect::<Vec<(f32, Keyboard)>>();
-                
+        .enumerate()
+        .map(|(group, mut state)| {
+            if let Some(result) = state.result { return Ok(result) }
+
+            let mut rng = ChaCha8Rng::from(&state.rng);
+            let id = group + 1;
+
+            if state.population.is_empty() {
+                println!("\ngroup {} of {} started\n", id, groups);
+                let random = |rng: &mut ChaCha8Rng| template
+                    .shuffled(rng)
+                    .constrained(rng)
+                    .expect("constraints were checked when the run was set up");
+                let first = start.clone().unwrap_or_else(|| random(&mut rng));
+                state.population = vec![first; population];
+                state.top = (0..survivors)
+                    .map(|_| random(&mut rng))
+                    .collect::<Vec<Keyboard>>()
+                    .into_par_iter()
+                    .map(|k| (score(&k), k))
+                    .collect::<Vec<(f32, Keyboard)>>();
+                state.score_history = vec![10000000000_f32; stagnation_window];
+            } else {
+                println!("\ngroup {} of {} resumed at generation {}\n", id, groups, state.generation);
+            }
 
-            let mut score_history: [f32; 100] = [10000000000.; 100];
-            let mut generation_count = 0_usize;
             loop {
-                let mut result = keyboards
+                let mut result = std::mem::take(&mut state.population)
                     .into_par_iter()
                     .map(|keyboard| {
-                        if let Some(entry) = top_50.iter()
-                                                   .find(|(_, k_cmp)| *k_cmp == keyboard) {
-                            entry.clone()
-                        } else {
-                           (read_dir(path.clone(), &keyboard).expect("you fucked up again"), keyboard.clone())
+                        if let Err(e) = keyboard.validate() {
+                            panic!("the search made an invalid keyboard, {}:\n{}", e, keyboard)
                         }
+                        (score(&keyboard), keyboard)
                     })
                     .collect::<Vec<(f32, Keyboard)>>();
 
                 result.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-                keyboards = result.iter().map(|(_, k)| k.clone()).collect();
-                top_50 = result.iter()
-                    .enumerate()
-                    .filter_map(|(i, data)| {
-                        if i < 50 { Some(data.clone()) }
-                        else { None }
-                    })
-                    .collect();
-
-                for (i, (_, keyboard)) in top_50.iter().enumerate() {
-                    let k = keyboards.get_mut(i + 50).expect("why did you do that?");
-                    *k = keyboard.reproduce(match i % 6 {
-                            0 => 1,
-                            1 => 2,
-                            2 => 4,
-                            3 => 8,
-                            4 => 16,
-                            5 => 32,
-                            _ => panic!()
-                        });
+                state.population = result.iter().map(|(_, k)| k.clone()).collect();
+                state.top = result[..survivors].to_vec();
+
+                for (i, k) in state.population.iter_mut().enumerate().skip(survivors) {
+                    let child = i - survivors;
+                    *k = state.top[child % survivors].1.reproduce(
+                        mutation_schedule[child % mutation_schedule.len()],
+                        &mut rng,
+                    );
                 }
 
-                generation_count += 1;
-
-                if generation_count % 4 == 0 {
-                    println!("\r{}% - {}",
-                        if id.to_string().len() == 1
-                        { format!(" {}", id.to_string()) }
-                        else 
-                        { id.to_string() },
-                        (1..=generation_count / 4)
-                            .into_iter()
-                            .map(|_| "*")
-                            .collect::<String>()
+                state.generation += 1;
+
+                if state.generation.is_multiple_of(4) {
+                    println!("\r{:>width$} - {}",
+                        id,
+                        "*".repeat(state.generation / 4),
+                        width = width,
                     );
                 }
 
-                score_history[generation_count % 100] = top_50[0].0;
-                if score_history.iter().all(|&s| s == score_history[0]) {
-                    break top_50[0];
+                state.score_history[state.generation % stagnation_window] = state.top[0].0;
+                if state.score_history.iter().all(|&s| s == state.score_history[0]) {
+                    state.result = Some(state.top[0].clone());
+                }
+
+                if state.result.is_some() || state.generation.is_multiple_of(checkpoint_interval) {
+                    state.rng = RngState::from(&rng);
+                    save(group, &state)?;
+                }
+
+                if let Some(result) = state.result {
+                    break Ok(result);
                 }
             }
         })
-        .collect();
+        .collect::<Result<_, CheckpointError>>()?;
 
     results.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-
-    for (score, keyboard) in results {
-        println!("Score: {}\n", score);
-        keyboard.print_self();
-        println!("\n");
-    }
-
+    Ok(results)
 }
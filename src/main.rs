@@ -1,5 +1,325 @@
-use keyboard_gen::go;
+use std::{error::Error, fs, path::{Path, PathBuf}, process, sync::Arc};
+
+use clap::{Args, Parser, Subcommand};
+use keyboard_gen::{resume, Checkpoint, Corpus, Fingering, GaConfig, Geometry, Keyboard, ScoreModel};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Parser)]
+#[command(about = "Genetic algorithm for generating keyboard layouts")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the genetic search and print the best layout of every group
+    Optimize {
+        #[command(flatten)]
+        corpus: CorpusArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        config: ConfigArgs,
+        /// Number of independent groups to evolve
+        #[arg(long)]
+        groups: Option<usize>,
+        /// Keyboards per generation in each group
+        #[arg(long)]
+        population: Option<usize>,
+        /// Keyboards kept as parents for the next generation
+        #[arg(long)]
+        survivors: Option<usize>,
+        /// Seed for the run; the same seed and corpus give the same result
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Start every group from this layout instead of a random one
+        #[arg(long)]
+        start: Option<String>,
+        #[command(flatten)]
+        geometry: GeometryArgs,
+        /// Save progress to this file so the run can be resumed
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+        #[command(flatten)]
+        results: ResultArgs,
+    },
+    /// Continue an optimize run from its checkpoint file
+    Resume {
+        #[command(flatten)]
+        corpus: CorpusArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Checkpoint written by `optimize --checkpoint`; kept up to date as the run goes on
+        #[arg(long)]
+        checkpoint: PathBuf,
+        #[command(flatten)]
+        results: ResultArgs,
+    },
+    /// Print the default run config as TOML
+    Config {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Score a layout against the corpus
+    Score {
+        #[command(flatten)]
+        corpus: CorpusArgs,
+        #[command(flatten)]
+        config: ConfigArgs,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Render a layout
+    Show {
+        #[command(flatten)]
+        layout: LayoutArgs,
+        /// Seed used by the `random` layout
+        #[arg(long)]
+        seed: Option<u64>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show two layouts side by side with the keys that differ highlighted
+    Diff {
+        #[command(flatten)]
+        layout: LayoutArgs,
+        /// Layout to compare with, on the same geometry
+        #[arg(long)]
+        other: String,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+#[derive(Args)]
+struct CorpusArgs {
+    /// Directory of text used to score layouts
+    #[arg(long, default_value = "pile")]
+    corpus: PathBuf,
+    /// Keep corpus statistics here so later runs over the same text skip the scan
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct GeometryArgs {
+    /// Keyboard geometry file; the built-in 47 key board if not given
+    #[arg(long)]
+    geometry: Option<PathBuf>,
+    /// Fingering file assigning keys of the geometry to other fingers
+    #[arg(long)]
+    fingering: Option<PathBuf>,
+}
+
+impl GeometryArgs {
+    fn load(&self) -> Result<Arc<Geometry>, Box<dyn Error>> {
+        let geometry = match &self.geometry {
+            Some(path) => Arc::new(Geometry::from_file(path)?),
+            None => Geometry::ansi_47(),
+        };
+        Ok(match &self.fingering {
+            Some(path) => Arc::new(geometry.with_fingering(&Fingering::from_file(path)?)?),
+            None => geometry,
+        })
+    }
+}
+
+#[derive(Args)]
+struct LayoutArgs {
+    /// Layout file, `random`, or a built-in layout: new_47, qwerty, dvorak,
+    /// colemak, colemak_dh, workman or norman
+    #[arg(long, default_value = "new_47")]
+    layout: String,
+    #[command(flatten)]
+    geometry: GeometryArgs,
+}
+
+impl LayoutArgs {
+    fn load(&self, seed: Option<u64>) -> Result<Keyboard, Box<dyn Error>> {
+        load_layout(&self.layout, &self.geometry.load()?, seed)
+    }
+}
+
+#[derive(Args)]
+struct ConfigArgs {
+    /// TOML run config; flags override its values
+    #[arg(long)]
+    config: Option<PathBuf>,
+}
+
+impl ConfigArgs {
+    fn load(&self) -> Result<GaConfig, Box<dyn Error>> {
+        Ok(match &self.config {
+            Some(path) => GaConfig::from_file(path)?,
+            None => GaConfig::default(),
+        })
+    }
+}
+
+#[derive(Args)]
+struct ResultArgs {
+    /// Save the best layout of every group as a layout file in this directory
+    #[arg(long)]
+    save_layouts: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// Also write the report to this file
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+fn load_layout(name: &str, geometry: &Arc<Geometry>, seed: Option<u64>) -> Result<Keyboard, Box<dyn Error>> {
+    let built_in = name == "random" || Keyboard::preset_names().any(|preset| preset == name);
+    if built_in && !geometry.is_ansi_47() {
+        return Err(format!("`{}` only exists on the built-in geometry", name).into())
+    }
+    let keyboard = match name {
+        "random" => Keyboard::new_random(&mut match seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        }),
+        name => match Keyboard::preset(name) {
+            Some(keyboard) => keyboard,
+            None => return Ok(Keyboard::from_file(Path::new(name), geometry.clone())
+                .map_err(|e| format!("{}: {}", name, e))?),
+        },
+    };
+    // the built-in layouts keep their keys under another fingering
+    Ok(Keyboard::with_geometry(geometry.clone(), keyboard.keys().to_vec()).expect("the same board"))
+}
+
+fn report(output: &Option<PathBuf>, text: &str) -> Result<(), Box<dyn Error>> {
+    print!("{}", text);
+    if let Some(path) = output {
+        fs::write(path, text)?;
+    }
+    Ok(())
+}
+
+fn report_results(
+    corpus: &Corpus,
+    results: &[(f32, Keyboard)],
+    seed: u64,
+    args: &ResultArgs,
+    output: &OutputArgs,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = &args.save_layouts {
+        fs::create_dir_all(dir)?;
+        let width = results.len().to_string().len();
+        for (i, (score, keyboard)) in results.iter().enumerate() {
+            let file = dir.join(format!("{:0width$}.layout", i + 1, width = width));
+            fs::write(file, format!("# score: {}\n{}", score, keyboard.to_layout_string()))?;
+        }
+    }
+
+    let mut text = format!("Seed: {}\n\n", seed);
+    for (score, keyboard) in results {
+        text.push_str(&format!("Score: {}\n{}\n\n{}\n\n\n", score, keyboard.stats(corpus), keyboard));
+    }
+    report(&output.output, &text)
+}
+
+fn load_corpus(args: &CorpusArgs) -> Result<Corpus, Box<dyn Error>> {
+    let path = &args.corpus;
+    if !path.is_dir() {
+        return Err(format!("corpus directory `{}` not found", path.display()).into());
+    }
+    Ok(match &args.cache_dir {
+        Some(cache_dir) => Corpus::from_dir_cached(path, cache_dir)?,
+        None => Corpus::from_dir(path)?,
+    })
+}
 
 fn main() {
-    go();
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Command::Optimize {
+            corpus, output, config, groups, population, survivors, seed, start, geometry, checkpoint, results,
+        } => {
+            let corpus = load_corpus(&corpus)?;
+            let mut config = config.load()?;
+            if let Some(groups) = groups { config.groups = groups }
+            if let Some(population) = population { config.population = population }
+            if let Some(survivors) = survivors { config.survivors = survivors }
+            if let Some(seed) = seed { config.seed = Some(seed) }
+            let seed = *config.seed.get_or_insert_with(rand::random);
+            config.validate()?;
+            let geometry = geometry.load()?;
+            let start = match start {
+                Some(name) => Some(load_layout(&name, &geometry, Some(seed))?),
+                None if !geometry.is_ansi_47() =>
+                    return Err("--start is needed to optimize a layout on another geometry".into()),
+                None => None,
+            };
+
+            let run = Checkpoint::new(&config, geometry, start.as_ref())?;
+            let model = ScoreModel::for_geometry(&config.weights, &run.geometry)?;
+            let run = resume(&corpus, &model, run, checkpoint.as_deref())?;
+            report_results(&corpus, &run, seed, &results, &output)
+        },
+        Command::Resume { corpus, output, checkpoint, results } => {
+            let corpus = load_corpus(&corpus)?;
+            let saved = Checkpoint::load(&checkpoint)?;
+            let seed = saved.config.seed.expect("checkpoints always record their seed");
+
+            let model = ScoreModel::for_geometry(&saved.config.weights, &saved.geometry)?;
+            let run = resume(&corpus, &model, saved, Some(&checkpoint))?;
+            report_results(&corpus, &run, seed, &results, &output)
+        },
+        Command::Score { corpus, config, layout, output } => {
+            let corpus = load_corpus(&corpus)?;
+            let keyboard = layout.load(None)?;
+            let model = ScoreModel::for_geometry(&config.load()?.weights, keyboard.geometry())?;
+
+            let breakdown = model.breakdown(&keyboard, &corpus);
+            // adding zero turns the -0 of an empty float sum into 0
+            let mut text = format!("Score: {}\n", breakdown.iter().map(|(_, cost)| cost).sum::<f64>() as f32 + 0.);
+            for (name, cost) in breakdown {
+                text.push_str(&format!("  {}: {}\n", name, cost as f32 + 0.));
+            }
+            text.push_str(&format!("{}\n\n{}\n", keyboard.stats(&corpus), keyboard));
+            report(&output.output, &text)
+        },
+        Command::Show { layout, seed, output } => {
+            let keyboard = layout.load(seed)?;
+            report(&output.output, &keyboard.to_layout_string())
+        },
+        Command::Diff { layout, other, output } => {
+            let keyboard = layout.load(None)?;
+            let other = load_layout(&other, &layout.geometry.load()?, None)?;
+
+            let render = || {
+                let mut text = keyboard.side_by_side(&other);
+                match keyboard.diff(&other) {
+                    Some(diff) => text.push_str(&format!("\n{}\n", diff)),
+                    None => text.push_str("\nthe layouts hold different keys\n"),
+                }
+                text
+            };
+            print!("{}", render());
+            // colour codes only go to the terminal
+            if let Some(path) = &output.output {
+                colored::control::set_override(false);
+                let text = render();
+                colored::control::unset_override();
+                fs::write(path, text)?;
+            }
+            Ok(())
+        },
+        Command::Config { output } => {
+            report(&output.output, &GaConfig::default().to_toml())
+        },
+    }
 }
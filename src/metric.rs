@@ -0,0 +1,479 @@
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ConfigError, Corpus, Finger, Geometry, Keyboard, Trigram};
+
+/// One criterion a keyboard is judged on. Lower costs are better.
+///
+/// Implement this to add your own criteria and combine them with the
+/// built-in ones in a [`ScoreModel`].
+pub trait Metric: Send + Sync {
+    fn name(&self) -> &str;
+    fn cost(&self, keyboard: &Keyboard, corpus: &Corpus) -> f64;
+}
+
+/// How much each finger's effort counts, e.g. more for the pinkies. Fingers
+/// not listed count once.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FingerWeights(pub BTreeMap<Finger, f32>);
+
+impl FingerWeights {
+    pub fn of(&self, finger: Option<Finger>) -> f32 {
+        finger.and_then(|f| self.0.get(&f)).copied().unwrap_or(1.)
+    }
+}
+
+/// Travel between keys: every bigram costs the `distance` between its keys,
+/// or the second key's `heatmap` value when the first character isn't on the
+/// keyboard, times the weight of the finger typing the second key.
+#[derive(Default)]
+pub struct Effort {
+    pub fingers: FingerWeights,
+}
+
+impl Metric for Effort {
+    fn name(&self) -> &str { "effort" }
+
+    fn cost(&self, keyboard: &Keyboard, corpus: &Corpus) -> f64 {
+        let index = keyboard.char_index();
+        corpus.bigrams()
+            .iter()
+            .map(|(&[a, b], &n)| {
+                let cost = match (index(a), index(b)) {
+                    (Some(a), Some(b)) => keyboard.distance(a, b) * self.fingers.of(keyboard.which_finger(b)),
+                    (None, Some(b)) => keyboard.geometry().heatmap()[b] * self.fingers.of(keyboard.which_finger(b)),
+                    (_, None) => 0.,
+                };
+                cost as f64 * n as f64
+            })
+            .sum()
+    }
+}
+
+/// Distance fingers move, in key widths, times the weight of the finger. A
+/// finger typing two keys in a row moves straight from one to the other;
+/// otherwise it comes from its home key.
+#[derive(Default)]
+pub struct Travel {
+    pub fingers: FingerWeights,
+}
+
+impl Metric for Travel {
+    fn name(&self) -> &str { "travel" }
+
+    fn cost(&self, keyboard: &Keyboard, corpus: &Corpus) -> f64 {
+        let geometry = keyboard.geometry();
+        let index = keyboard.char_index();
+        corpus.bigrams()
+            .iter()
+            .filter_map(|(&[a, b], &n)| {
+                let b = index(b)?;
+                let finger = geometry.finger_of(b)?;
+                let from = index(a)
+                    .filter(|&a| geometry.finger_of(a) == Some(finger))
+                    .or(geometry.home_of(finger))?;
+                Some((geometry.travel(from, b)? * self.fingers.of(Some(finger))) as f64 * n as f64)
+            })
+            .sum()
+    }
+}
+
+/// Keystrokes a finger takes beyond its share of the corpus. `max_load` gives
+/// the most each finger should type, in percent of all keystrokes on the
+/// keyboard; fingers not listed have no limit.
+pub struct FingerLoad {
+    pub max_load: BTreeMap<Finger, f32>,
+}
+
+impl Metric for FingerLoad {
+    fn name(&self) -> &str { "overload" }
+
+    fn cost(&self, keyboard: &Keyboard, corpus: &Corpus) -> f64 {
+        let index = keyboard.char_index();
+        let mut load: BTreeMap<Finger, u64> = BTreeMap::new();
+        for (&c, &n) in corpus.unigrams() {
+            if let Some(finger) = index(c).and_then(|i| keyboard.which_finger(i)) {
+                *load.entry(finger).or_default() += n;
+            }
+        }
+        let total = load.values().sum::<u64>() as f64;
+
+        self.max_load
+            .iter()
+            .map(|(finger, &max)| {
+                let presses = load.get(finger).copied().unwrap_or(0) as f64;
+                (presses - total * max as f64 / 100.).max(0.)
+            })
+            .sum()
+    }
+}
+
+/// Number of same finger bigrams.
+pub struct SameFingerBigrams;
+
+impl Metric for SameFingerBigrams {
+    fn name(&self) -> &str { "sfb" }
+
+    fn cost(&self, keyboard: &Keyboard, corpus: &Corpus) -> f64 {
+        let index = keyboard.char_index();
+        corpus.bigrams()
+            .iter()
+            .filter_map(|(&[a, b], &n)| keyboard.is_sfb(index(a)?, index(b)?).then_some(n as f64))
+            .sum()
+    }
+}
+
+/// Presses of shift. Shift is held by the pinky of the other hand than the
+/// one typing the character, or the same hand with `same_hand`. Every
+/// shifted character costs one, and one more for each neighbour the shifting
+/// pinky has to type too: the character itself, or the one before it.
+/// Shifted characters following one shifted by the same pinky are free, as
+/// shift is still held.
+pub struct Shift {
+    pub same_hand: bool,
+}
+
+impl Metric for Shift {
+    fn name(&self) -> &str { "shift" }
+
+    fn cost(&self, keyboard: &Keyboard, corpus: &Corpus) -> f64 {
+        let index = keyboard.char_index();
+        let shifted = |c: char, i: usize| {
+            keyboard.keys()[i].chars().is_some_and(|(base, shift)| shift == c && base != c)
+        };
+        let shift_finger = |i: usize| {
+            let hand = keyboard.which_hand(i)?;
+            Some(if self.same_hand { hand.pinky() } else { hand.other().pinky() })
+        };
+
+        corpus.bigrams()
+            .iter()
+            .filter_map(|(&[a, b], &n)| {
+                let b_index = index(b)?;
+                if !shifted(b, b_index) { return None }
+                let pinky = shift_finger(b_index)?;
+
+                let a_index = index(a);
+                if a_index.is_some_and(|i| shifted(a, i) && shift_finger(i) == Some(pinky)) {
+                    return None
+                }
+                let busy = [Some(b_index), a_index]
+                    .iter()
+                    .filter(|i| i.and_then(|i| keyboard.which_finger(i)) == Some(pinky))
+                    .count();
+                Some((1 + busy) as f64 * n as f64)
+            })
+            .sum()
+    }
+}
+
+/// Switches to a layer beyond base and shift. Reaching a layer costs one plus
+/// the heatmap value of the access key held for it, preferably one on the
+/// other hand, and one more for each neighbour the holding finger has to type
+/// too, as with [`Shift`]. Characters following one on the same layer are
+/// free, as the key is still held.
+pub struct LayerSwitch;
+
+impl Metric for LayerSwitch {
+    fn name(&self) -> &str { "layers" }
+
+    fn cost(&self, keyboard: &Keyboard, corpus: &Corpus) -> f64 {
+        if keyboard.layers().is_empty() { return 0. }
+        let index = keyboard.char_index();
+        let layer_of = keyboard.char_layer();
+        let access: Vec<Vec<usize>> = (0..keyboard.layers().len()).map(|l| keyboard.access_keys(l)).collect();
+
+        corpus.bigrams()
+            .iter()
+            .filter_map(|(&[a, b], &n)| {
+                let layer = layer_of(b)?;
+                if layer_of(a) == Some(layer) { return None }
+                let b_index = index(b)?;
+
+                let hand = keyboard.which_hand(b_index);
+                let held = *access[layer]
+                    .iter()
+                    .find(|&&k| keyboard.which_hand(k) != hand)
+                    .or(access[layer].first())?;
+                let finger = keyboard.which_finger(held);
+
+                let busy = [Some(b_index), index(a)]
+                    .iter()
+                    .filter(|i| i.and_then(|i| keyboard.which_finger(i)) == finger)
+                    .count();
+                let press = keyboard.geometry().heatmap()[held] as f64;
+                Some((1. + press + busy as f64) * n as f64)
+            })
+            .sum()
+    }
+}
+
+/// Trigrams, each costing the amount given for its [`Trigram`] kind.
+pub struct TrigramFlow {
+    /// Cost of each kind, in [`Trigram::ALL`] order.
+    pub costs: [f32; 6],
+}
+
+impl Metric for TrigramFlow {
+    fn name(&self) -> &str { "trigrams" }
+
+    fn cost(&self, keyboard: &Keyboard, corpus: &Corpus) -> f64 {
+        let index = keyboard.char_index();
+        corpus.trigrams()
+            .iter()
+            .filter_map(|(&[a, b, c], &n)| {
+                let kind = keyboard.classify_trigram(index(a)?, index(b)?, index(c)?)?;
+                Some(self.costs[kind as usize] as f64 * n as f64)
+            })
+            .sum()
+    }
+}
+
+/// How far a keyboard is from a `reference` layout its users already know,
+/// counted over the keys of the reference's base layer. Every key typed from
+/// another position costs `moved`, plus `changed_finger` if another finger
+/// types it and `changed_hand` if the other hand does. Keys moved beyond
+/// `max_moved` cost `over_max_moved` more each.
+pub struct Learnability {
+    pub reference: Keyboard,
+    pub moved: f32,
+    pub changed_finger: f32,
+    pub changed_hand: f32,
+    pub max_moved: Option<usize>,
+    pub over_max_moved: f32,
+}
+
+impl Metric for Learnability {
+    fn name(&self) -> &str { "learnability" }
+
+    fn cost(&self, keyboard: &Keyboard, _: &Corpus) -> f64 {
+        let index = keyboard.char_index();
+        let mut moved = 0_usize;
+        let mut cost = 0.;
+        for (from, key) in self.reference.keys().iter().enumerate() {
+            let Some((c, _)) = key.chars() else { continue };
+            let to = index(c);
+            if to == Some(from) { continue }
+
+            moved += 1;
+            cost += self.moved;
+            let finger = to.and_then(|i| keyboard.which_finger(i));
+            if finger != self.reference.which_finger(from) { cost += self.changed_finger }
+            if finger.map(Finger::hand) != self.reference.which_hand(from) { cost += self.changed_hand }
+        }
+        let over = self.max_moved.map_or(0, |max| moved.saturating_sub(max));
+        cost as f64 + over as f64 * self.over_max_moved as f64
+    }
+}
+
+/// Settings of the [`Learnability`] metric, the `[weights.learnability]`
+/// table of a run config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LearnabilityWeights {
+    /// The layout to stay close to: a preset like `qwerty`, or a layout file.
+    pub reference: String,
+    pub moved: f32,
+    pub changed_finger: f32,
+    pub changed_hand: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_moved: Option<usize>,
+    pub over_max_moved: f32,
+}
+
+impl Default for LearnabilityWeights {
+    fn default() -> Self {
+        Self {
+            reference: "new_47".to_string(),
+            moved: 0.,
+            changed_finger: 0.,
+            changed_hand: 0.,
+            max_moved: None,
+            over_max_moved: 1000.,
+        }
+    }
+}
+
+impl LearnabilityWeights {
+    /// The reference layout on `geometry`.
+    pub fn reference(&self, geometry: &Arc<Geometry>) -> Result<Keyboard, ConfigError> {
+        let invalid = |e: &dyn std::fmt::Display| ConfigError::Invalid(format!("reference layout `{}`: {}", self.reference, e));
+        // the run's pins say nothing about where the reference has its keys
+        let geometry = geometry.with_moves(None, None, BTreeMap::new(), vec![]).map_err(|e| invalid(&e))?;
+        match Keyboard::preset(&self.reference) {
+            Some(preset) if geometry.is_ansi_47() => Ok(preset.on(Arc::new(geometry))),
+            Some(_) => Err(invalid(&"presets only exist on the built-in geometry")),
+            None => Keyboard::from_file(Path::new(&self.reference), Arc::new(geometry)).map_err(|e| invalid(&e)),
+        }
+    }
+
+    fn is_off(&self) -> bool {
+        self.moved == 0. && self.changed_finger == 0. && self.changed_hand == 0. && self.max_moved.is_none()
+    }
+}
+
+fn is_empty(weights: &FingerWeights) -> bool { weights.0.is_empty() }
+
+/// Weights of the built-in metrics, and how shift is held, as set in the
+/// `[weights]` table of a run config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoreWeights {
+    pub effort: f32,
+    /// Multiplies the `effort` and `travel` of each finger.
+    #[serde(skip_serializing_if = "is_empty")]
+    pub finger_effort: FingerWeights,
+    /// Cost of every key width a finger moves, from the geometry's key
+    /// positions rather than its heatmap.
+    pub travel: f32,
+    /// Cost of every same finger bigram, on top of its effort.
+    pub sfb: f32,
+    /// Cost of pressing shift, see [`Shift`].
+    pub shift: f32,
+    /// Hold shift with the hand typing the shifted character rather than the
+    /// other one.
+    pub same_hand_shift: bool,
+    /// Cost of switching to a layer beyond base and shift, see
+    /// [`LayerSwitch`].
+    pub layer_switch: f32,
+    /// Cost of every keystroke a finger takes beyond its `finger_max_load`.
+    pub overload: f32,
+    /// Most of the keystrokes each finger should take, in percent.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub finger_max_load: BTreeMap<Finger, f32>,
+    pub alternate: f32,
+    pub inward_roll: f32,
+    pub outward_roll: f32,
+    pub one_hand: f32,
+    pub redirect: f32,
+    pub other_trigram: f32,
+    /// Cost of moving away from a reference layout, see [`Learnability`].
+    #[serde(skip_serializing_if = "LearnabilityWeights::is_off")]
+    pub learnability: LearnabilityWeights,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            effort: 1.,
+            finger_effort: FingerWeights::default(),
+            travel: 0.,
+            sfb: 2.,
+            shift: 1.,
+            same_hand_shift: false,
+            layer_switch: 1.,
+            overload: 1.,
+            finger_max_load: BTreeMap::new(),
+            alternate: 0.,
+            inward_roll: 0.,
+            outward_roll: 0.25,
+            one_hand: 0.25,
+            redirect: 1.,
+            other_trigram: 0.,
+            learnability: LearnabilityWeights::default(),
+        }
+    }
+}
+
+impl ScoreWeights {
+    pub(crate) fn all(&self) -> Vec<f32> {
+        let weights = [
+            self.effort, self.travel, self.sfb, self.shift, self.layer_switch, self.overload,
+            self.alternate, self.inward_roll, self.outward_roll, self.one_hand, self.redirect, self.other_trigram,
+            self.learnability.moved, self.learnability.changed_finger, self.learnability.changed_hand,
+            self.learnability.over_max_moved,
+        ];
+        weights.into_iter().chain(self.finger_effort.0.values().copied()).collect()
+    }
+
+    fn trigram_costs(&self) -> [f32; 6] {
+        Trigram::ALL.map(|kind| match kind {
+            Trigram::Alternate => self.alternate,
+            Trigram::InwardRoll => self.inward_roll,
+            Trigram::OutwardRoll => self.outward_roll,
+            Trigram::OneHand => self.one_hand,
+            Trigram::Redirect => self.redirect,
+            Trigram::Other => self.other_trigram,
+        })
+    }
+}
+
+/// A weighted sum of metrics. The optimizer minimizes whatever model it is
+/// given.
+pub struct ScoreModel {
+    metrics: Vec<(f32, Box<dyn Metric>)>,
+}
+
+impl Default for ScoreModel {
+    fn default() -> Self {
+        Self::from_weights(&ScoreWeights::default())
+    }
+}
+
+impl ScoreModel {
+    /// A model with no metrics; add them with [`ScoreModel::with`].
+    pub fn empty() -> Self {
+        Self { metrics: vec![] }
+    }
+
+    /// The built-in metrics with the given weights. Metrics weighted zero are
+    /// left out, and so is [`Learnability`], which needs a reference layout;
+    /// [`ScoreModel::for_geometry`] adds it.
+    pub fn from_weights(weights: &ScoreWeights) -> Self {
+        let model = Self::empty()
+            .with(weights.effort, Effort { fingers: weights.finger_effort.clone() })
+            .with(weights.travel, Travel { fingers: weights.finger_effort.clone() })
+            .with(weights.sfb, SameFingerBigrams)
+            .with(weights.shift, Shift { same_hand: weights.same_hand_shift })
+            .with(weights.layer_switch, LayerSwitch);
+        let model = match weights.finger_max_load.is_empty() {
+            true => model,
+            false => model.with(weights.overload, FingerLoad { max_load: weights.finger_max_load.clone() }),
+        };
+
+        let costs = weights.trigram_costs();
+        if costs.iter().any(|&c| c != 0.) {
+            model.with(1., TrigramFlow { costs })
+        } else {
+            model
+        }
+    }
+
+    /// The built-in metrics with the given weights for keyboards on
+    /// `geometry`, including [`Learnability`] if it is weighted.
+    pub fn for_geometry(weights: &ScoreWeights, geometry: &Arc<Geometry>) -> Result<Self, ConfigError> {
+        let model = Self::from_weights(weights);
+        let learnability = &weights.learnability;
+        if learnability.is_off() { return Ok(model) }
+
+        Ok(model.with(1., Learnability {
+            reference: learnability.reference(geometry)?,
+            moved: learnability.moved,
+            changed_finger: learnability.changed_finger,
+            changed_hand: learnability.changed_hand,
+            max_moved: learnability.max_moved,
+            over_max_moved: learnability.over_max_moved,
+        }))
+    }
+
+    pub fn with(mut self, weight: f32, metric: impl Metric + 'static) -> Self {
+        if weight != 0. {
+            self.metrics.push((weight, Box::new(metric)));
+        }
+        self
+    }
+
+    pub fn score(&self, keyboard: &Keyboard, corpus: &Corpus) -> f32 {
+        self.breakdown(keyboard, corpus).iter().map(|(_, cost)| cost).sum::<f64>() as f32
+    }
+
+    /// The weighted cost of each metric, in the order they were added.
+    pub fn breakdown(&self, keyboard: &Keyboard, corpus: &Corpus) -> Vec<(&str, f64)> {
+        self.metrics
+            .iter()
+            .map(|(weight, metric)| (metric.name(), *weight as f64 * metric.cost(keyboard, corpus)))
+            .collect()
+    }
+}
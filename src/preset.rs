@@ -0,0 +1,29 @@
+//! Well known layouts on the built-in 47 key geometry, to score as baselines
+//! and start the optimizer from. They live in `layouts/presets`.
+
+use crate::Keyboard;
+
+/// Every preset but `new_47`, by name.
+const PRESETS: [(&str, &str); 6] = [
+    ("qwerty", include_str!("../layouts/presets/qwerty.layout")),
+    ("dvorak", include_str!("../layouts/presets/dvorak.layout")),
+    ("colemak", include_str!("../layouts/presets/colemak.layout")),
+    ("colemak_dh", include_str!("../layouts/presets/colemak_dh.layout")),
+    ("workman", include_str!("../layouts/presets/workman.layout")),
+    ("norman", include_str!("../layouts/presets/norman.layout")),
+];
+
+impl Keyboard {
+    /// Names of the built-in layouts [`Keyboard::preset`] knows.
+    pub fn preset_names() -> impl Iterator<Item = &'static str> {
+        std::iter::once("new_47").chain(PRESETS.iter().map(|&(name, _)| name))
+    }
+
+    /// The built-in layout called `name`, like `qwerty` or `colemak`, on the
+    /// built-in geometry.
+    pub fn preset(name: &str) -> Option<Keyboard> {
+        if name == "new_47" { return Some(Keyboard::new_47()) }
+        let &(_, layout) = PRESETS.iter().find(|&&(preset, _)| preset == name)?;
+        Some(layout.parse().expect("presets are valid layouts"))
+    }
+}
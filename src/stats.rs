@@ -0,0 +1,154 @@
+use std::fmt;
+
+use crate::{Corpus, Finger, Hand, Keyboard};
+
+/// How three consecutive keys move between the hands and fingers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigram {
+    /// Left, right, left or right, left, right.
+    Alternate,
+    /// Two keys on one hand moving towards the index finger, one on the other hand.
+    InwardRoll,
+    /// Two keys on one hand moving towards the pinky, one on the other hand.
+    OutwardRoll,
+    /// All three keys on one hand, moving in one direction.
+    OneHand,
+    /// All three keys on one hand, changing direction part way.
+    Redirect,
+    /// Uses the same finger twice in a row.
+    Other,
+}
+
+impl Trigram {
+    pub const ALL: [Trigram; 6] = [
+        Trigram::Alternate,
+        Trigram::InwardRoll,
+        Trigram::OutwardRoll,
+        Trigram::OneHand,
+        Trigram::Redirect,
+        Trigram::Other,
+    ];
+
+    /// Classifies a trigram from the fingers typing it.
+    pub fn from_fingers(f1: Finger, f2: Finger, f3: Finger) -> Self {
+        let left = |f: Finger| f.hand() == Hand::Left;
+        // towards the index finger of the hand
+        let inward = |a: Finger, b: Finger| if left(a) { b > a } else { b < a };
+
+        if left(f1) != left(f2) && left(f2) != left(f3) {
+            return Trigram::Alternate
+        }
+        if f1 == f2 || f2 == f3 {
+            return Trigram::Other
+        }
+        if left(f1) == left(f2) && left(f2) == left(f3) {
+            return if inward(f1, f2) == inward(f2, f3) { Trigram::OneHand } else { Trigram::Redirect }
+        }
+
+        let (a, b) = if left(f1) == left(f2) { (f1, f2) } else { (f2, f3) };
+        if inward(a, b) { Trigram::InwardRoll } else { Trigram::OutwardRoll }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Trigram::Alternate => "Alternate",
+            Trigram::InwardRoll => "Inward roll",
+            Trigram::OutwardRoll => "Outward roll",
+            Trigram::OneHand => "One hand",
+            Trigram::Redirect => "Redirect",
+            Trigram::Other => "Other",
+        }
+    }
+}
+
+/// Measurements of how a keyboard types a corpus, reported next to its score.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LayoutStats {
+    /// Bigrams with both characters on the keyboard.
+    pub bigrams: u64,
+    /// Bigrams typed by the same finger on two different keys.
+    pub sfb: u64,
+    /// Trigrams with all three characters on the keyboard.
+    pub trigrams: u64,
+    /// Trigram counts by kind, in [`Trigram::ALL`] order.
+    pub trigram_kinds: [u64; 6],
+    /// Characters typed on the keyboard.
+    pub keystrokes: u64,
+    /// Keystrokes of each finger, in [`Finger::ALL`] order.
+    pub finger_load: [u64; 10],
+}
+
+fn percent(n: u64, total: u64) -> f64 {
+    if total == 0 { 0. } else { n as f64 / total as f64 * 100. }
+}
+
+impl LayoutStats {
+    pub fn sfb_percent(&self) -> f64 {
+        percent(self.sfb, self.bigrams)
+    }
+
+    pub fn trigram_count(&self, kind: Trigram) -> u64 {
+        self.trigram_kinds[kind as usize]
+    }
+
+    pub fn trigram_percent(&self, kind: Trigram) -> f64 {
+        percent(self.trigram_count(kind), self.trigrams)
+    }
+
+    /// Share of the keystrokes typed by `finger`.
+    pub fn load_percent(&self, finger: Finger) -> f64 {
+        percent(self.finger_load[finger as usize], self.keystrokes)
+    }
+}
+
+impl fmt::Display for LayoutStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SFB: {:.2}%", self.sfb_percent())?;
+        for kind in Trigram::ALL {
+            write!(f, "  {}: {:.2}%", kind.name(), self.trigram_percent(kind))?;
+        }
+        write!(f, "\nLoad:")?;
+        for finger in Finger::ALL {
+            write!(f, "  {:?}: {:.2}%", finger, self.load_percent(finger))?;
+        }
+        Ok(())
+    }
+}
+
+impl Keyboard {
+    /// Whether typing `a` then `b` uses the same finger for two different keys.
+    pub fn is_sfb(&self, a: usize, b: usize) -> bool {
+        a != b && self.which_finger(a).is_some() && self.which_finger(a) == self.which_finger(b)
+    }
+
+    pub fn classify_trigram(&self, a: usize, b: usize, c: usize) -> Option<Trigram> {
+        Some(Trigram::from_fingers(self.which_finger(a)?, self.which_finger(b)?, self.which_finger(c)?))
+    }
+
+    pub fn stats(&self, corpus: &Corpus) -> LayoutStats {
+        let index = self.char_index();
+        let mut stats = LayoutStats::default();
+
+        for (&c, &n) in corpus.unigrams() {
+            if let Some(finger) = index(c).and_then(|i| self.which_finger(i)) {
+                stats.keystrokes += n;
+                stats.finger_load[finger as usize] += n;
+            }
+        }
+        for (&[a, b], &n) in corpus.bigrams() {
+            if let (Some(a), Some(b)) = (index(a), index(b)) {
+                stats.bigrams += n;
+                if self.is_sfb(a, b) { stats.sfb += n }
+            }
+        }
+        for (&[a, b, c], &n) in corpus.trigrams() {
+            if let (Some(a), Some(b), Some(c)) = (index(a), index(b), index(c)) {
+                if let Some(kind) = self.classify_trigram(a, b, c) {
+                    stats.trigrams += n;
+                    stats.trigram_kinds[kind as usize] += n;
+                }
+            }
+        }
+        stats
+    }
+}
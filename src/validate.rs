@@ -0,0 +1,85 @@
+//! Checks that a keyboard is well formed: every character typed by one key
+//! only, the whole alphabet there, keys shifting to what they should and
+//! pinned characters where they were pinned.
+
+use std::{collections::BTreeSet, fmt};
+
+use crate::{Key, Keyboard};
+
+/// Characters every keyboard must be able to type.
+const REQUIRED: std::ops::RangeInclusive<char> = 'a'..='z';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyboardError {
+    /// `c` is on more than one key or layer position.
+    Duplicate(char),
+    /// A character every keyboard needs is nowhere on it.
+    Missing(char),
+    /// The key at `position` shifts to the wrong character: a letter to
+    /// anything but its capital, or a key to itself.
+    BadShift { position: usize, base: char, shift: char },
+    /// A pinned character is not at its position.
+    PinMoved { key: char, position: usize },
+}
+
+impl fmt::Display for KeyboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyboardError::Duplicate(c) => write!(f, "`{}` is on more than one key", c.escape_default()),
+            KeyboardError::Missing(c) => write!(f, "`{}` is not on the keyboard", c),
+            KeyboardError::BadShift { position, base, shift } =>
+                write!(f, "key {} shifts `{}` to `{}`", position, base.escape_default(), shift.escape_default()),
+            KeyboardError::PinMoved { key, position } => write!(f, "`{}` is pinned to key {} but has moved", key, position),
+        }
+    }
+}
+
+impl std::error::Error for KeyboardError {}
+
+/// Whether `shift` is what `base` should shift to.
+fn shift_pair(key: &Key) -> bool {
+    match *key {
+        Key::Letter(base, shift) => {
+            let mut upper = base.to_uppercase();
+            match (upper.next(), upper.next()) {
+                (Some(capital), None) if capital != base => shift == capital,
+                // letters without a single capital only need to shift to something
+                _ => shift != base,
+            }
+        },
+        Key::Number(base, shift) | Key::Punctuation(base, shift) => shift != base,
+        Key::Special(_, _) | Key::Layer(_) => true,
+    }
+}
+
+impl Keyboard {
+    /// Checks the keyboard is well formed, returning the first problem found.
+    pub fn validate(&self) -> Result<(), KeyboardError> {
+        for (position, key) in self.keys.iter().enumerate() {
+            if let (false, Some((base, shift))) = (shift_pair(key), key.chars()) {
+                return Err(KeyboardError::BadShift { position, base, shift })
+            }
+        }
+
+        let mut seen = BTreeSet::new();
+        let base = self.keys.iter().flat_map(|k| {
+            // special keys type the same character shifted
+            let (base, shift) = k.chars().unzip();
+            base.into_iter().chain(shift.filter(|_| !matches!(k, Key::Special(_, _))))
+        });
+        let layers = self.layers.iter().flat_map(|layer| layer.keys.iter().flatten().copied());
+        if let Some(c) = base.chain(layers).find(|&c| !seen.insert(c)) {
+            return Err(KeyboardError::Duplicate(c))
+        }
+        if let Some(c) = REQUIRED.into_iter().find(|c| !seen.contains(c)) {
+            return Err(KeyboardError::Missing(c))
+        }
+
+        for (&key, &position) in &self.geometry.moves().pinned {
+            if !self.keys[position].match_char(key) {
+                return Err(KeyboardError::PinMoved { key, position })
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+//! Helpers shared by the integration tests. Each test file uses some of them.
+#![allow(dead_code)]
+
+use std::{path::Path, sync::Arc};
+
+use keyboard_gen::{GaConfig, Geometry, Keyboard};
+
+/// `new_47` with the keys at each pair of positions swapped in turn.
+pub fn swapped(swaps: &[(usize, usize)]) -> Keyboard {
+    let keyboard = Keyboard::new_47();
+    let mut keys = keyboard.keys().to_vec();
+    for &(a, b) in swaps { keys.swap(a, b) }
+    Keyboard::with_geometry(keyboard.geometry().clone(), keys).unwrap()
+}
+
+/// `new_47` on the built-in geometry with the `[moves]` of `config`.
+pub fn with_moves(config: &str) -> Keyboard {
+    let config = GaConfig::from_toml(config).unwrap();
+    let geometry = config.moves.apply(Keyboard::new_47().geometry()).unwrap();
+    Keyboard::with_geometry(geometry, Keyboard::new_47().keys().to_vec()).unwrap()
+}
+
+/// `layouts/split_34.layout` on its geometry, with symbols and numbers on
+/// two layers.
+pub fn split_34() -> Keyboard {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let geometry = Arc::new(Geometry::from_file(&root.join("geometries/split_34.toml")).unwrap());
+    Keyboard::from_file(&root.join("layouts/split_34.layout"), geometry).unwrap()
+}
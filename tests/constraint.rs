@@ -0,0 +1,84 @@
+use keyboard_gen::{Constraint, GaConfig, Hand, Keyboard};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+mod common;
+
+use common::{split_34, with_moves};
+
+const CONFIG: &str = r#"
+[[moves.constraints]]
+rule = "hand"
+key = "i"
+hand = "Right"
+
+[[moves.constraints]]
+rule = "adjacent"
+keys = ["q", "u"]
+"#;
+
+/// `new_47` on the built-in geometry with the constraints of `CONFIG`,
+/// after swapping the keys at each pair of positions.
+fn constrained(swaps: &[(usize, usize)]) -> Keyboard {
+    let keyboard = with_moves(CONFIG);
+    let mut keys = keyboard.keys().to_vec();
+    for &(a, b) in swaps { keys.swap(a, b) }
+    Keyboard::with_geometry(keyboard.geometry().clone(), keys).unwrap()
+}
+
+#[test]
+fn parse_constraints() {
+    let config = GaConfig::from_toml(CONFIG).unwrap();
+    assert_eq!(config.moves.constraints, [
+        Constraint::Hand { key: 'i', hand: Hand::Right },
+        Constraint::Adjacent { keys: ['q', 'u'] },
+    ]);
+    assert_eq!(GaConfig::from_toml(&config.to_toml()).unwrap(), config);
+    assert!(GaConfig::from_toml("[[moves.constraints]]\nrule = \"row\"\nkey = \"a\"\n").is_err());
+}
+
+#[test]
+fn unmet_constraints() {
+    // new_47 has `i` under the left middle finger and `q` and `u` apart
+    let keyboard = constrained(&[]);
+    assert_eq!(keyboard.unmet_constraints().len(), 2);
+    assert!(!keyboard.meets_constraints());
+
+    let geometry = Keyboard::new_47().geometry().clone();
+    let i = Keyboard::new_47().char_to_index('i').unwrap();
+    assert!(Constraint::Hand { key: 'i', hand: Hand::Left }.holds(&Keyboard::new_47()));
+    assert!(Constraint::Row { key: 'i', row: geometry.row_of(i).unwrap() }.holds(&Keyboard::new_47()));
+    assert!(Constraint::Adjacent { keys: ['a', 'r'] }.holds(&Keyboard::new_47()));
+    assert!(!Constraint::Adjacent { keys: ['a', '`'] }.holds(&Keyboard::new_47()));
+}
+
+#[test]
+fn adjacent_by_position() {
+    let adjacent = |keyboard: &Keyboard, keys| Constraint::Adjacent { keys }.holds(keyboard);
+    // tab and space are listed one after the other but sit far apart
+    assert!(!adjacent(&Keyboard::new_47(), ['\t', ' ']));
+    // the last key of a row and the first of the next
+    assert!(!adjacent(&Keyboard::new_47(), ['\\', 'a']));
+
+    let split = split_34();
+    // column stagger, but one key apart
+    assert!(adjacent(&split, ['f', 'g']));
+    assert!(adjacent(&split, ['w', 'e']));
+    // either side of the gap between the halves
+    assert!(!adjacent(&split, ['t', 'y']));
+    assert!(!adjacent(&split, ['g', 'h']));
+}
+
+#[test]
+fn mutations_keep_constraints() {
+    // `i` to the right middle finger and `u` next to `q`
+    let mut keyboard = constrained(&[(15, 21), (42, 24)]);
+    assert!(keyboard.meets_constraints(), "{}", keyboard);
+    assert!(Constraint::LettersOnly { positions: vec![19, 20] }.holds(&keyboard));
+
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    for _ in 0..1000 {
+        keyboard = keyboard.reproduce(8, &mut rng);
+        assert!(keyboard.meets_constraints(), "{}", keyboard);
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use keyboard_gen::Corpus;
+
+/// A fresh directory under the system's temporary one, holding a small
+/// corpus in `text` and nothing yet in `cache`.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("keyboard_gen_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("text/more")).unwrap();
+    fs::write(dir.join("text/a.txt"), "the quick brown fox").unwrap();
+    fs::write(dir.join("text/more/b.txt"), "jumps over the lazy dog").unwrap();
+    fs::write(dir.join("text/c.bin"), [0xff, 0xfe, 0x00]).unwrap();
+    dir
+}
+
+/// The one cache file in `dir`.
+fn cache_file(dir: &Path) -> PathBuf {
+    let files: Vec<PathBuf> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).collect();
+    assert_eq!(files.len(), 1, "{:?}", files);
+    files[0].clone()
+}
+
+#[test]
+fn cache_round_trip() {
+    let dir = scratch("cache_round_trip");
+    let (text, cache) = (dir.join("text"), dir.join("cache"));
+    let corpus = Corpus::from_dir(&text).unwrap();
+    assert_eq!(corpus.trigrams().get(&['f', 'o', 'x']), Some(&1));
+
+    assert_eq!(Corpus::from_dir_cached(&text, &cache).unwrap(), corpus);
+    assert_eq!(Corpus::from_dir_cached(&text, &cache).unwrap(), corpus);
+
+    // the count of the last trigram is the file's last eight bytes, so a
+    // changed count shows the tables came from the cache
+    let file = cache_file(&cache);
+    let mut bytes = fs::read(&file).unwrap();
+    let end = bytes.len();
+    bytes[end - 8] += 1;
+    fs::write(&file, bytes).unwrap();
+    let (&last, &n) = corpus.trigrams().last_key_value().unwrap();
+    assert_eq!(Corpus::from_dir_cached(&text, &cache).unwrap().trigrams()[&last], n + 1);
+
+    // other text is kept under another name
+    fs::write(text.join("a.txt"), "pack my box").unwrap();
+    assert_eq!(Corpus::from_dir_cached(&text, &cache).unwrap(), Corpus::from_dir(&text).unwrap());
+    assert_eq!(fs::read_dir(&cache).unwrap().count(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn truncated_cache() {
+    let dir = scratch("truncated_cache");
+    let (text, cache) = (dir.join("text"), dir.join("cache"));
+    let corpus = Corpus::from_dir_cached(&text, &cache).unwrap();
+    let file = cache_file(&cache);
+    let bytes = fs::read(&file).unwrap();
+
+    for len in [0, 6, 12, bytes.len() / 2, bytes.len() - 1] {
+        fs::write(&file, &bytes[..len]).unwrap();
+        assert_eq!(Corpus::from_dir_cached(&text, &cache).unwrap(), corpus, "cut to {} bytes", len);
+        // and the cache is written again
+        assert_eq!(fs::read(&file).unwrap(), bytes);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cache_key_covers_large_files() {
+    let dir = scratch("cache_key_covers_large_files");
+    let (text, cache) = (dir.join("text"), dir.join("cache"));
+    // longer than the chunks files are hashed in, changed only at the end
+    let mut long = "the quick brown fox ".repeat(10_000);
+    fs::write(text.join("long.txt"), &long).unwrap();
+    Corpus::from_dir_cached(&text, &cache).unwrap();
+    long.push('!');
+    fs::write(text.join("long.txt"), &long).unwrap();
+    assert_eq!(Corpus::from_dir_cached(&text, &cache).unwrap(), Corpus::from_dir(&text).unwrap());
+    assert_eq!(fs::read_dir(&cache).unwrap().count(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
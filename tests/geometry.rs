@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+
+use keyboard_gen::{Corpus, Finger, Fingering, Geometry, Hand, Keyboard, Metric, Travel};
+use Finger::*;
+
+fn repo_file(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+fn load(path: &str) -> Geometry {
+    Geometry::from_file(&repo_file(path)).unwrap()
+}
+
+/// The fingers of the built-in board, row by row, written out independently
+/// of `geometries/ansi_47.toml`.
+const ANSI_47: [&[Finger]; 5] = [
+    &[LPinky, LPinky, LRing, LMid, LIndex, LIndex, RIndex, RIndex, RMid, RRing, RPinky, RPinky, RPinky],
+    &[LPinky, LRing, LMid, LIndex, LIndex, RIndex, RIndex, RMid, RRing, RPinky, RPinky, RPinky, RPinky],
+    &[LPinky, LRing, LMid, LIndex, LIndex, RIndex, RIndex, RMid, RRing, RPinky, RPinky],
+    &[LPinky, LRing, LMid, LIndex, LIndex, RIndex, RIndex, RMid, RRing, RPinky],
+    &[LPinky, RThumb, RPinky, RPinky],
+];
+
+#[test]
+fn ansi_47_every_key() {
+    let geometry = Geometry::ansi_47();
+    let mut index = 0;
+    for (row, fingers) in ANSI_47.iter().enumerate() {
+        for (column, &finger) in fingers.iter().enumerate() {
+            assert_eq!(geometry.finger_of(index), Some(finger), "finger of {}", index);
+            assert_eq!(geometry.hand_of(index), Some(finger.hand()), "hand of {}", index);
+            assert_eq!(geometry.row_of(index), Some(row), "row of {}", index);
+            assert_eq!(geometry.column_of(index), Some(column), "column of {}", index);
+            index += 1;
+        }
+    }
+    assert_eq!(geometry.len(), index);
+}
+
+#[test]
+fn ansi_47_number_row() {
+    // ring and middle finger keys of the number row once reported row 1
+    let geometry = Geometry::ansi_47();
+    for index in [2, 3, 8, 9] {
+        assert_eq!(geometry.row_of(index), Some(0), "row of {}", index);
+    }
+    assert_eq!(geometry.finger_of(2), Some(LRing));
+    assert_eq!(geometry.finger_of(3), Some(LMid));
+    assert_eq!(geometry.finger_of(8), Some(RMid));
+    assert_eq!(geometry.finger_of(9), Some(RRing));
+}
+
+#[test]
+fn ansi_47_hands() {
+    let geometry = Geometry::ansi_47();
+    let left: Vec<usize> = (0..geometry.len()).filter(|&i| geometry.hand_of(i) == Some(Hand::Left)).collect();
+    assert_eq!(left, [0, 1, 2, 3, 4, 5, 13, 14, 15, 16, 17, 26, 27, 28, 29, 30, 37, 38, 39, 40, 41, 47]);
+}
+
+#[test]
+fn out_of_range() {
+    for geometry in [(*Geometry::ansi_47()).clone(), load("geometries/split_3x6.toml")] {
+        for index in [geometry.len(), geometry.len() + 1, usize::MAX] {
+            assert_eq!(geometry.finger_of(index), None);
+            assert_eq!(geometry.hand_of(index), None);
+            assert_eq!(geometry.row_of(index), None);
+            assert_eq!(geometry.column_of(index), None);
+        }
+    }
+}
+
+#[test]
+fn finger_hands() {
+    for finger in Finger::ALL {
+        let left = matches!(finger, LPinky | LRing | LMid | LIndex | LThumb);
+        assert_eq!(finger.hand(), if left { Hand::Left } else { Hand::Right }, "{:?}", finger);
+        assert_eq!(finger.is_thumb(), matches!(finger, LThumb | RThumb), "{:?}", finger);
+    }
+    assert_eq!(Hand::Left.pinky(), LPinky);
+    assert_eq!(Hand::Right.pinky(), RPinky);
+    assert_eq!(Hand::Left.other(), Hand::Right);
+    assert_eq!(Hand::Right.other(), Hand::Left);
+}
+
+#[test]
+fn split_3x6_every_key() {
+    let geometry = load("geometries/split_3x6.toml");
+    let half = [LPinky, LPinky, LRing, LMid, LIndex, LIndex];
+    let row: Vec<Finger> = half.iter().copied().chain(half.iter().rev().map(|&f| mirror(f))).collect();
+
+    for index in 0..36 {
+        assert_eq!(geometry.row_of(index), Some(index / 12), "row of {}", index);
+        assert_eq!(geometry.column_of(index), Some(index % 12), "column of {}", index);
+        assert_eq!(geometry.finger_of(index), Some(row[index % 12]), "finger of {}", index);
+        let hand = if index % 12 < 6 { Hand::Left } else { Hand::Right };
+        assert_eq!(geometry.hand_of(index), Some(hand), "hand of {}", index);
+    }
+    for (column, finger) in [LThumb, LThumb, RThumb, RThumb].into_iter().enumerate() {
+        let index = 36 + column;
+        assert_eq!(geometry.row_of(index), Some(3));
+        assert_eq!(geometry.column_of(index), Some(column));
+        assert_eq!(geometry.finger_of(index), Some(finger));
+    }
+    assert_eq!(geometry.len(), 40);
+}
+
+fn mirror(finger: Finger) -> Finger {
+    match finger {
+        LPinky => RPinky,
+        LRing => RRing,
+        LMid => RMid,
+        LIndex => RIndex,
+        LThumb => RThumb,
+        RThumb => LThumb,
+        RIndex => LIndex,
+        RMid => LMid,
+        RRing => LRing,
+        RPinky => LPinky,
+    }
+}
+
+#[test]
+fn rows_and_columns_agree() {
+    for path in ["geometries/ansi_47.toml", "geometries/ortho_4x12.toml", "geometries/split_3x6.toml", "geometries/split_34.toml"] {
+        let geometry = load(path);
+        for index in 0..geometry.len() {
+            let row = geometry.row_of(index).unwrap();
+            let column = geometry.column_of(index).unwrap();
+            assert_eq!(geometry.row_range(row).start + column, index, "{} key {}", path, index);
+            assert!(geometry.row_range(row).contains(&index), "{} key {}", path, index);
+        }
+    }
+}
+
+#[test]
+fn fingering_overrides_fingers() {
+    let geometry = Geometry::ansi_47();
+    let fingering = Fingering::from_file(&repo_file("fingerings/angle_mod.toml")).unwrap();
+    let angled = geometry.with_fingering(&fingering).unwrap();
+
+    for index in 0..geometry.len() {
+        let expected = match index {
+            37 => LRing,
+            38 => LMid,
+            39 => LIndex,
+            _ => geometry.finger_of(index).unwrap(),
+        };
+        assert_eq!(angled.finger_of(index), Some(expected), "finger of {}", index);
+        assert_eq!(angled.row_of(index), geometry.row_of(index));
+        assert_eq!(angled.column_of(index), geometry.column_of(index));
+    }
+    assert!(angled.is_ansi_47());
+}
+
+#[test]
+fn fingering_out_of_range() {
+    let fingering = Fingering::from_toml("[fingers]\n51 = \"LPinky\"\n").unwrap();
+    assert!(Geometry::ansi_47().with_fingering(&fingering).is_err());
+    assert!(Fingering::from_toml("[fingers]\nq = \"LPinky\"\n").is_err());
+}
+
+#[test]
+fn keyboard_agrees_with_geometry() {
+    let keyboard = Keyboard::new_47();
+    let geometry = keyboard.geometry();
+    for index in 0..geometry.len() + 1 {
+        assert_eq!(keyboard.which_finger(index), geometry.finger_of(index));
+        assert_eq!(keyboard.which_hand(index), geometry.hand_of(index));
+        assert_eq!(keyboard.index_to_row(index), geometry.row_of(index));
+    }
+}
+
+/// Five keys measured in millimetres, two of them 20 mm apart.
+const TINY: &str = r#"
+name = "tiny"
+units = "mm"
+key_size = 20.0
+
+[homes]
+RIndex = 2
+
+[[rows]]
+keys = 3
+x = [0.0, 20.0, 60.0]
+y = 0.0
+heatmap = [1.0, 0.5, 2.0]
+fingers = ["LIndex", "LIndex", "RIndex"]
+
+[[rows]]
+keys = 2
+x = [0.0, 60.0]
+y = 40.0
+heatmap = [0.25, 0.0]
+fingers = ["LIndex", "RIndex"]
+"#;
+
+#[test]
+fn positions_and_travel() {
+    let geometry = Geometry::from_toml(TINY).unwrap();
+    assert_eq!(geometry.position(1), Some((1., 0.)));
+    assert_eq!(geometry.position(4), Some((3., 2.)));
+    assert_eq!(geometry.position(5), None);
+    assert_eq!(geometry.travel(0, 1), Some(1.));
+    assert_eq!(geometry.travel(1, 3), Some(5_f32.sqrt()));
+    assert_eq!(geometry.travel(4, 2), Some(2.));
+    assert_eq!(geometry.travel(0, 5), None);
+
+    // the same numbers in key widths, or with a smaller key
+    let keys = Geometry::from_toml(&TINY.replace("units = \"mm\"\n", "")).unwrap();
+    assert_eq!(keys.position(1), Some((20., 0.)));
+    let small = Geometry::from_toml(&TINY.replace("key_size = 20.0", "key_size = 10.0")).unwrap();
+    assert_eq!(small.position(1), Some((2., 0.)));
+    assert!(Geometry::from_toml(&TINY.replace("key_size = 20.0", "key_size = 0.0")).is_err());
+}
+
+#[test]
+fn home_keys() {
+    let geometry = Geometry::from_toml(TINY).unwrap();
+    // as given, though key 4 is easier
+    assert_eq!(geometry.home_of(RIndex), Some(2));
+    // otherwise the finger's easiest key
+    assert_eq!(geometry.home_of(LIndex), Some(3));
+    assert_eq!(geometry.home_of(LPinky), None);
+    assert!(Geometry::from_toml(&TINY.replace("RIndex = 2", "RIndex = 1")).is_err());
+
+    assert_eq!(Geometry::ansi_47().home_of(LIndex), Some(29));
+    assert_eq!(Geometry::ansi_47().home_of(RThumb), Some(48));
+}
+
+#[test]
+fn travel_metric() {
+    // on new_47 `e` is the left index finger's home key, `c` is next to it
+    // and `o` is a row up, typed by the same finger
+    let keyboard = Keyboard::new_47();
+    let geometry = keyboard.geometry();
+    let cost = |text: &str| Travel::default().cost(&keyboard, &Corpus::from_text(text));
+    assert_eq!(cost("ec"), 1.);
+    // from home, unless the finger typed the key before
+    assert_eq!(cost("tc"), 1.);
+    assert_eq!(cost("éc"), 1.);
+    assert_eq!(cost("oc"), geometry.travel(16, 30).unwrap() as f64);
+    assert_eq!(cost("cé"), 0.);
+    assert_eq!(cost("ee"), 0.);
+}
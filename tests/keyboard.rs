@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+
+use keyboard_gen::{Key, Keyboard, Place};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+mod common;
+
+use common::{split_34, swapped};
+
+#[test]
+fn equality_and_fingerprint() {
+    let keyboard = Keyboard::new_47();
+    let swapped = swapped(&[(15, 16)]);
+
+    assert_eq!(keyboard, Keyboard::new_47());
+    assert_eq!(keyboard.fingerprint(), Keyboard::new_47().fingerprint());
+    // one swap leaves most keys in place, which `!=` once got wrong
+    assert!(keyboard != swapped);
+    assert_ne!(keyboard.fingerprint(), swapped.fingerprint());
+}
+
+#[test]
+fn fingerprints_tell_layouts_apart() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let keyboards: HashSet<Keyboard> = (0..1000).map(|_| Keyboard::new_random(&mut rng)).collect();
+    let fingerprints: HashSet<u64> = keyboards.iter().map(Keyboard::fingerprint).collect();
+    assert_eq!(fingerprints.len(), keyboards.len());
+}
+
+#[test]
+fn diff() {
+    let keyboard = Keyboard::new_47();
+    assert!(keyboard.diff(&keyboard).unwrap().is_empty());
+
+    let diff = keyboard.diff(&swapped(&[(15, 16)])).unwrap();
+    assert_eq!(diff.changed(), 2);
+    assert_eq!(diff.cycles, [vec![15, 16]]);
+    assert_eq!(diff.moved, [(keyboard.keys()[15], 15, 16), (keyboard.keys()[16], 16, 15)]);
+
+    // 15 to 16, 16 to 20 and 20 to 15, plus a separate swap
+    let diff = keyboard.diff(&swapped(&[(15, 16), (15, 20), (40, 41)])).unwrap();
+    assert_eq!(diff.changed(), 5);
+    assert_eq!(diff.cycles, [vec![15, 16, 20], vec![40, 41]]);
+    assert_eq!(diff.to_string(), "5 of 51 positions changed\n  cycle i -> o -> n -> i\n  swap m x");
+
+    let mut keys = keyboard.keys().to_vec();
+    keys[15] = keys[16];
+    let other = Keyboard::with_geometry(keyboard.geometry().clone(), keys).unwrap();
+    assert_eq!(keyboard.diff(&other), None);
+}
+
+#[test]
+fn diff_layers() {
+    let keyboard = split_34();
+    let geometry = keyboard.geometry().clone();
+
+    // `!` and `@` swapped on the symbol layer
+    let mut layers = keyboard.layers().to_vec();
+    layers[0].keys.swap(0, 1);
+    let other = keyboard.clone().with_layers(layers.clone()).unwrap();
+    let diff = keyboard.diff(&other).unwrap();
+    assert_eq!(diff.changed(), 2);
+    assert!(diff.moved.is_empty());
+    assert_eq!(diff.chars_moved, [('!', Place::Layer(0, 0), Place::Layer(0, 1)), ('@', Place::Layer(0, 1), Place::Layer(0, 0))]);
+    assert_eq!(diff.to_string(), "2 of 102 positions changed\n  move ! @sym 0 -> @sym 1\n  move @ @sym 1 -> @sym 0");
+
+    // then `,` traded for `@`, keeping its `<`
+    let mut keys = keyboard.keys().to_vec();
+    assert_eq!(keys[27], Key::Punctuation(',', '<'));
+    keys[27] = Key::Punctuation('@', '<');
+    layers[0].keys[0] = Some(',');
+    let other = Keyboard::with_geometry(geometry.clone(), keys).unwrap().with_layers(layers).unwrap();
+    let diff = keyboard.diff(&other).unwrap();
+    assert_eq!(diff.changed(), 3);
+    assert_eq!(diff.chars_moved, [
+        ('!', Place::Layer(0, 0), Place::Layer(0, 1)),
+        (',', Place::Base(27), Place::Layer(0, 0)),
+        ('@', Place::Layer(0, 1), Place::Base(27)),
+    ]);
+
+    // then `m` moved there, and the changed key where `m` was
+    let mut keys = other.keys().to_vec();
+    keys.swap(26, 27);
+    let other = Keyboard::with_geometry(geometry, keys).unwrap().with_layers(other.layers().to_vec()).unwrap();
+    let diff = keyboard.diff(&other).unwrap();
+    assert_eq!(diff.moved, [(Key::Letter('m', 'M'), 26, 27)]);
+    assert!(diff.cycles.is_empty());
+    assert_eq!(diff.to_string(), [
+        "4 of 102 positions changed",
+        "  move m 26 -> 27",
+        "  move ! @sym 0 -> @sym 1",
+        "  move , 27 -> @sym 0",
+        "  move < 27 shifted -> 26 shifted",
+        "  move @ @sym 1 -> 26",
+    ].join("\n"));
+}
+
+#[test]
+fn presets() {
+    for name in Keyboard::preset_names() {
+        let keyboard = Keyboard::preset(name).unwrap();
+        assert_eq!(keyboard.validate(), Ok(()), "{}", name);
+        assert!(keyboard.geometry().is_ansi_47(), "{}", name);
+    }
+    assert_eq!(Keyboard::preset("new_47"), Some(Keyboard::new_47()));
+    assert_eq!(Keyboard::preset("azerty"), None);
+
+    let qwerty = Keyboard::preset("qwerty").unwrap();
+    let home: String = qwerty.keys()[26..37].iter().map(|k| k.chars().unwrap().0).collect();
+    assert_eq!(home, "asdfghjkl;'");
+    let colemak = Keyboard::preset("colemak").unwrap();
+    assert_eq!(qwerty.diff(&colemak).unwrap().changed(), 17);
+}
@@ -0,0 +1,51 @@
+use keyboard_gen::{Key, Keyboard, LayoutError};
+
+mod common;
+
+use common::{split_34, swapped};
+
+#[test]
+fn round_trip() {
+    let keyboard = Keyboard::new_47();
+    assert_eq!(keyboard.to_layout_string().parse::<Keyboard>().unwrap(), keyboard);
+    // `3` first puts `#` at the start of the shifted grid's top row
+    let keyboard = swapped(&[(0, 3)]);
+    let text = keyboard.to_layout_string();
+    assert!(text.lines().any(|l| l.starts_with("\\#")), "{}", text);
+    assert_eq!(text.parse::<Keyboard>().unwrap(), keyboard);
+}
+
+#[test]
+fn comments() {
+    let text = format!("# a comment\n  #indented too\n{}", Keyboard::new_47().to_layout_string());
+    assert_eq!(text.parse::<Keyboard>().unwrap(), Keyboard::new_47());
+}
+
+#[test]
+fn layers() {
+    let keyboard = split_34();
+    let names: Vec<&str> = keyboard.layers().iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, ["sym", "num"]);
+    assert_eq!(keyboard.keys()[30], Key::Layer(1));
+    assert_eq!(keyboard.keys()[33], Key::Layer(0));
+    assert_eq!(keyboard.access_keys(0), [33]);
+    assert_eq!(keyboard.layer_of('!'), Some(0));
+    assert_eq!(keyboard.layer_of('7'), Some(1));
+    assert_eq!(keyboard.layer_of('a'), None);
+    assert_eq!(keyboard.layers()[0].keys[0], Some('!'));
+    assert_eq!(keyboard.layers()[1].keys[0], None);
+    assert_eq!(Keyboard::parse(&keyboard.to_layout_string(), keyboard.geometry().clone()).unwrap(), keyboard);
+}
+
+#[test]
+fn bad_layers() {
+    let base = "q w e r t y u i o p\na s d f g h j k l ;\nz x c v b n m , . /\n@sym spc ent bsp\n";
+    let geometry = split_34().geometry().clone();
+    let parse = |text: &str| Keyboard::parse(text, geometry.clone());
+    assert!(matches!(parse(base), Err(LayoutError::UnknownLayer(name)) if name == "sym"));
+    let sym = "@sym\n! @ # $ % ^ & * ( )\n--- --- --- --- --- --- --- --- --- ---\n--- --- --- --- --- --- --- --- --- ---\n--- --- --- ---\n";
+    assert!(parse(&format!("{}\n{}", base, sym)).is_ok());
+    assert!(matches!(parse(&format!("{}\n{}\n{}", base, sym, sym)), Err(LayoutError::DuplicateLayer(_))));
+    let unreached = sym.replace("@sym", "@nav");
+    assert!(matches!(parse(&format!("{}\n{}\n{}", base, sym, unreached)), Err(LayoutError::NoAccessKey(name)) if name == "nav"));
+}
@@ -0,0 +1,143 @@
+use keyboard_gen::{
+    Corpus, Effort, Finger, FingerLoad, FingerWeights, GaConfig, Keyboard, LayerSwitch, Learnability, Metric,
+    ScoreModel, Shift, Travel,
+};
+
+mod common;
+
+use common::{split_34, swapped};
+
+fn learnability(max_moved: Option<usize>) -> Learnability {
+    Learnability {
+        reference: Keyboard::new_47(),
+        moved: 1.,
+        changed_finger: 10.,
+        changed_hand: 100.,
+        max_moved,
+        over_max_moved: 1000.,
+    }
+}
+
+#[test]
+fn learnability_counts_moves() {
+    let corpus = Corpus::from_text("");
+    let metric = learnability(None);
+    assert_eq!(metric.cost(&Keyboard::new_47(), &corpus), 0.);
+    // `i` and `o` sit under the left middle and index fingers
+    assert_eq!(metric.cost(&swapped(&[(15, 16)]), &corpus), 2. * (1. + 10.));
+    // `i` and `f`, under the left middle and right index fingers
+    assert_eq!(metric.cost(&swapped(&[(15, 19)]), &corpus), 2. * (1. + 10. + 100.));
+    // `=` and `f`, both under the right index finger
+    assert_eq!(metric.cost(&swapped(&[(18, 19)]), &corpus), 2.);
+
+    let capped = learnability(Some(3));
+    assert_eq!(capped.cost(&swapped(&[(18, 19)]), &corpus), 2.);
+    assert_eq!(capped.cost(&swapped(&[(18, 19), (15, 16)]), &corpus), 4. + 20. + 1000.);
+}
+
+#[test]
+fn learnability_from_config() {
+    let config = GaConfig::from_toml("[weights.learnability]\nmoved = 1.0\n").unwrap();
+    let corpus = Corpus::from_text("the quick brown fox");
+    let geometry = Keyboard::new_47().geometry().clone();
+    let with = ScoreModel::for_geometry(&config.weights, &geometry).unwrap();
+    let without = ScoreModel::from_weights(&config.weights);
+
+    assert_eq!(with.score(&Keyboard::new_47(), &corpus), without.score(&Keyboard::new_47(), &corpus));
+    let keyboard = swapped(&[(18, 19)]);
+    assert_eq!(with.score(&keyboard, &corpus), without.score(&keyboard, &corpus) + 2.);
+    assert_eq!(GaConfig::from_toml(&config.to_toml()).unwrap(), config);
+}
+
+#[test]
+fn layer_switches() {
+    let keyboard = split_34();
+    let cost = |text: &str| LayerSwitch.cost(&keyboard, &Corpus::from_text(text));
+
+    assert_eq!(LayerSwitch.cost(&Keyboard::new_47(), &Corpus::from_text("a!")), 0.);
+    assert_eq!(cost("as"), 0.);
+    // `!` on the left pinky, `@sym` held by the right thumb at 0.5
+    assert_eq!(cost("a!"), 1.5);
+    // staying on the layer is free
+    assert_eq!(cost("!@"), 0.);
+    // `7` on the left ring finger, and `@num` is only on the left thumb,
+    // which also types `spc` before it
+    assert_eq!(cost("a7"), 1.5);
+    assert_eq!(cost(" 7"), 2.5);
+}
+
+#[test]
+fn shifts() {
+    // on new_47 `a` is under the left pinky, `e` the left index finger and
+    // `s` the right pinky
+    let table = [
+        // unshifted characters are free
+        ("ae", false, 0.),
+        // shift from the other hand's pinky
+        ("eE", false, 1.),
+        ("eA", false, 1.),
+        ("eS", false, 1.),
+        // one more when that pinky types the character before
+        ("sA", false, 2.),
+        ("aS", false, 2.),
+        // with `same_hand`, from the pinky of the character's own hand,
+        // which is busy if it types the character itself
+        ("eE", true, 1.),
+        ("eA", true, 2.),
+        ("eS", true, 2.),
+        ("aE", true, 2.),
+        ("sE", true, 1.),
+        // shift still held by the same pinky
+        ("eAE", false, 1.),
+        ("eAE", true, 2.),
+        // but not by the other one
+        ("eES", false, 2.),
+        ("eES", true, 3.),
+    ];
+    for (text, same_hand, cost) in table {
+        let shift = Shift { same_hand };
+        assert_eq!(shift.cost(&Keyboard::new_47(), &Corpus::from_text(text)), cost, "{:?} same_hand: {}", text, same_hand);
+    }
+}
+
+#[test]
+fn finger_load() {
+    // `a` under the left pinky four times and `e` under the left index
+    // finger once; `é` isn't on the keyboard
+    let corpus = Corpus::from_text("aaaaeé");
+    let cost = |max_load: &[(Finger, f32)]| {
+        FingerLoad { max_load: max_load.iter().copied().collect() }.cost(&Keyboard::new_47(), &corpus)
+    };
+    assert_eq!(cost(&[]), 0.);
+    // 40% of five keystrokes is two, so two too many
+    assert_eq!(cost(&[(Finger::LPinky, 40.)]), 2.);
+    assert_eq!(cost(&[(Finger::LPinky, 40.), (Finger::LIndex, 50.)]), 2.);
+    assert_eq!(cost(&[(Finger::LPinky, 80.)]), 0.);
+    assert_eq!(cost(&[(Finger::LPinky, 0.), (Finger::LIndex, 0.)]), 5.);
+}
+
+#[test]
+fn finger_weights() {
+    let weights = FingerWeights([(Finger::LPinky, 2.)].into_iter().collect());
+    assert_eq!(weights.of(Some(Finger::LPinky)), 2.);
+    assert_eq!(weights.of(Some(Finger::RPinky)), 1.);
+    assert_eq!(weights.of(None), 1.);
+
+    // `[` is under the left pinky, `c` under the left index finger
+    let keyboard = Keyboard::new_47();
+    let (pinky, index) = (Corpus::from_text("e["), Corpus::from_text("[c"));
+    let effort = Effort { fingers: weights.clone() };
+    let travel = Travel { fingers: weights };
+    for (weighted, plain) in [(&effort as &dyn Metric, &Effort::default() as &dyn Metric), (&travel, &Travel::default())] {
+        assert!(plain.cost(&keyboard, &pinky) > 0., "{}", plain.name());
+        assert_eq!(weighted.cost(&keyboard, &pinky), 2. * plain.cost(&keyboard, &pinky), "{}", plain.name());
+        assert_eq!(weighted.cost(&keyboard, &index), plain.cost(&keyboard, &index), "{}", plain.name());
+    }
+
+    // `finger_effort` weighs both
+    let config = GaConfig::from_toml("[weights]\ntravel = 1.0\n\n[weights.finger_effort]\nLPinky = 2.0\n").unwrap();
+    let model = ScoreModel::from_weights(&config.weights);
+    let breakdown = model.breakdown(&keyboard, &pinky);
+    assert_eq!(breakdown[0], ("effort", effort.cost(&keyboard, &pinky)));
+    assert_eq!(breakdown[1], ("travel", travel.cost(&keyboard, &pinky)));
+}
@@ -0,0 +1,122 @@
+use std::{path::Path, sync::Arc};
+
+use keyboard_gen::{Checkpoint, GaConfig, Geometry, Key, Keyboard};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+mod common;
+
+use common::{split_34, with_moves};
+
+#[test]
+fn movable_override() {
+    // none of the geometry's punctuation positions, but `/` at 46
+    let movable = [15, 16, 19, 20, 28, 30, 35, 39, 40, 41, 42, 43, 44, 46];
+    let mut keyboard = with_moves(&format!("[moves]\nmovable = {:?}\n", movable));
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    for _ in 0..1000 {
+        keyboard = keyboard.reproduce(8, &mut rng);
+    }
+    for (i, (key, start)) in keyboard.keys().iter().zip(Keyboard::new_47().keys()).enumerate() {
+        assert!(key == start || movable.contains(&i), "key {} moved: {}", i, keyboard);
+    }
+    assert_eq!(keyboard.validate(), Ok(()));
+}
+
+#[test]
+fn punctuation_override() {
+    let config = "[moves]\nmovable = [15, 16, 19, 20, 46]\npunctuation = [16, 46]\n";
+    let mut keyboard = with_moves(config);
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    for _ in 0..1000 {
+        keyboard = keyboard.reproduce(4, &mut rng);
+    }
+    assert_eq!(keyboard.validate(), Ok(()));
+    let config = GaConfig::from_toml(config).unwrap();
+    assert_eq!(GaConfig::from_toml(&config.to_toml()).unwrap(), config);
+    assert!(GaConfig::from_toml("[moves]\nmovable = [15, 16]\npunctuation = [46]\n").is_err());
+}
+
+#[test]
+fn preset_start_moves_its_letters() {
+    let config = GaConfig { seed: Some(0), ..GaConfig::default() };
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    for name in Keyboard::preset_names() {
+        let preset = Keyboard::preset(name).unwrap();
+        let checkpoint = Checkpoint::new(&config, preset.geometry().clone(), Some(&preset)).unwrap();
+        let start = checkpoint.start.unwrap();
+        let mut moved = vec![false; start.keys().len()];
+        let mut keyboard = start.clone();
+        for _ in 0..1000 {
+            keyboard = keyboard.reproduce(4, &mut rng);
+            for (i, (key, was)) in keyboard.keys().iter().zip(start.keys()).enumerate() {
+                moved[i] |= key != was;
+            }
+        }
+        for (i, key) in start.keys().iter().enumerate().filter(|(_, k)| matches!(k, Key::Letter(_, _))) {
+            assert!(moved[i], "{} never moves {:?}", name, key);
+        }
+    }
+}
+
+#[test]
+fn symbols_move_between_layers() {
+    let split = split_34();
+    // `;`, `,`, `.` and `/` in layouts/split_34.layout
+    let config = GaConfig::from_toml("[moves]\nmovable = [19, 27, 28, 29]\n").unwrap();
+    let geometry = config.moves.apply(split.geometry()).unwrap();
+    let start = Keyboard::with_geometry(geometry, split.keys().to_vec()).unwrap().with_layers(split.layers().to_vec()).unwrap();
+
+    let mut keyboard = start.clone();
+    let (mut on, mut off) = (false, false);
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    for _ in 0..1000 {
+        keyboard = keyboard.reproduce(4, &mut rng);
+        assert_eq!(keyboard.validate(), Ok(()), "{}", keyboard.to_layout_string());
+        on |= keyboard.layer_of('!').is_none();
+        off |= keyboard.layer_of(';').is_some();
+    }
+    assert!(on && off);
+    assert!(keyboard.layers()[1].keys.iter().flatten().all(|c| c.is_ascii_digit()));
+}
+
+#[test]
+fn thumb_keys_swap_among_themselves() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let geometry = Arc::new(Geometry::from_file(&root.join("geometries/split_3x6.toml")).unwrap());
+    let start = Keyboard::from_file(&root.join("layouts/split_3x6.layout"), geometry.clone()).unwrap();
+    // tab, space, enter and backspace, on the four thumb keys
+    let thumbs = 36..40;
+    let specials = |keyboard: &Keyboard| -> Vec<usize> {
+        (0..keyboard.keys().len()).filter(|&i| matches!(keyboard.keys()[i], Key::Special(_, _))).collect()
+    };
+    assert_eq!(specials(&start), Vec::from_iter(thumbs.clone()));
+
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut keyboard = start.clone();
+    let mut swapped = false;
+    for _ in 0..1000 {
+        keyboard = keyboard.reproduce(4, &mut rng);
+        assert_eq!(specials(&keyboard), Vec::from_iter(thumbs.clone()), "{}", keyboard.to_layout_string());
+        swapped |= thumbs.clone().any(|i| keyboard.keys()[i] != start.keys()[i]);
+    }
+    assert!(swapped);
+
+    let shuffles: Vec<Keyboard> = (0..20).map(|_| start.shuffled(&mut rng)).collect();
+    for shuffled in &shuffles {
+        assert_eq!(specials(shuffled), Vec::from_iter(thumbs.clone()), "{}", shuffled.to_layout_string());
+    }
+    assert!(shuffles.iter().any(|k| thumbs.clone().any(|i| k.keys()[i] != start.keys()[i])));
+
+    // a pinned thumb key stays put
+    let config = GaConfig::from_toml("[moves]\npinned = { \" \" = 37 }\n").unwrap();
+    let pinned = Keyboard::with_geometry(config.moves.apply(&geometry).unwrap(), start.keys().to_vec()).unwrap();
+    let mut keyboard = pinned.clone();
+    for _ in 0..1000 {
+        keyboard = keyboard.reproduce(4, &mut rng);
+        assert_eq!(keyboard.keys()[37], start.keys()[37]);
+    }
+    for _ in 0..20 {
+        assert_eq!(pinned.shuffled(&mut rng).keys()[37], start.keys()[37]);
+    }
+}
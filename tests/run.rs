@@ -0,0 +1,169 @@
+use std::{
+    collections::HashSet,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use keyboard_gen::{go, resume, Checkpoint, CheckpointError, Corpus, GaConfig, Keyboard, Metric, ScoreModel};
+
+mod common;
+
+use common::split_34;
+
+/// Where `e` is, remembering every keyboard it was asked to cost. It takes
+/// a while, so groups asking for the same keyboard ask at the same time.
+#[derive(Clone, Default)]
+struct Counting {
+    costed: Arc<Mutex<Vec<Keyboard>>>,
+}
+
+impl Metric for Counting {
+    fn name(&self) -> &str {
+        "counting"
+    }
+
+    fn cost(&self, keyboard: &Keyboard, _: &Corpus) -> f64 {
+        self.costed.lock().unwrap().push(keyboard.clone());
+        thread::sleep(Duration::from_millis(2));
+        keyboard.char_to_index('e').unwrap() as f64
+    }
+}
+
+/// Costs nothing until it has been asked `limit` times, then stops the run
+/// by panicking.
+struct Interrupt {
+    calls: AtomicUsize,
+    limit: usize,
+}
+
+impl Metric for Interrupt {
+    fn name(&self) -> &str {
+        "interrupt"
+    }
+
+    fn cost(&self, _: &Keyboard, _: &Corpus) -> f64 {
+        if self.calls.fetch_add(1, Ordering::SeqCst) >= self.limit { panic!("interrupted") }
+        0.
+    }
+}
+
+fn small_config() -> GaConfig {
+    GaConfig {
+        groups: 4,
+        population: 20,
+        survivors: 10,
+        stagnation_window: 5,
+        seed: Some(0),
+        ..GaConfig::default()
+    }
+}
+
+#[test]
+fn scores_each_keyboard_once() {
+    let counting = Counting::default();
+    let model = ScoreModel::empty().with(1., counting.clone());
+    let checkpoint = Checkpoint::new(&small_config(), Keyboard::new_47().geometry().clone(), None).unwrap();
+    // several threads even on one core, so misses can overlap
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(8).build().unwrap();
+    pool.install(|| resume(&Corpus::from_text(""), &model, checkpoint, None)).unwrap();
+
+    let costed = counting.costed.lock().unwrap();
+    let distinct: HashSet<&Keyboard> = costed.iter().collect();
+    assert!(costed.len() > 100);
+    assert_eq!(costed.len(), distinct.len());
+}
+
+#[test]
+fn resumed_run_ends_like_an_uninterrupted_one() {
+    let config = GaConfig { checkpoint_interval: 2, ..small_config() };
+    let corpus = Corpus::from_text("the quick brown fox jumps over the lazy dog, then sleeps");
+    let geometry = Keyboard::new_47().geometry().clone();
+    let expected = go(&corpus, &config, None).unwrap();
+
+    let path = std::env::temp_dir().join(format!("keyboard_gen_resume_{}.json", std::process::id()));
+    let interrupted = ScoreModel::for_geometry(&config.weights, &geometry)
+        .unwrap()
+        .with(1., Interrupt { calls: AtomicUsize::new(0), limit: 200 });
+    let checkpoint = Checkpoint::new(&config, geometry.clone(), None).unwrap();
+    // one group at a time, so the interruption lands in the same place on any machine
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let run = pool.install(|| {
+        panic::catch_unwind(AssertUnwindSafe(|| resume(&corpus, &interrupted, checkpoint, Some(&path))))
+    });
+    assert!(run.is_err());
+
+    let checkpoint = Checkpoint::load(&path).unwrap();
+    // a finished group and one saved part way
+    assert!(checkpoint.groups[0].result.is_some());
+    assert!(checkpoint.groups.iter().any(|group| group.generation > 0 && group.result.is_none()));
+    let model = ScoreModel::for_geometry(&config.weights, &geometry).unwrap();
+    let resumed = resume(&corpus, &model, checkpoint, Some(&path)).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(resumed, expected);
+}
+
+#[test]
+fn random_layouts_keep_to_movable() {
+    let config = GaConfig { moves: GaConfig::from_toml("[moves]\nmovable = [15, 16]\n").unwrap().moves, ..small_config() };
+    let results = go(&Corpus::from_text("the quick brown fox"), &config, None).unwrap();
+    let built_in = Keyboard::new_47();
+    for (_, keyboard) in results {
+        for (i, (key, was)) in keyboard.keys().iter().zip(built_in.keys()).enumerate() {
+            assert!(key == was || [15, 16].contains(&i), "key {} moved: {}", i, keyboard);
+        }
+    }
+}
+
+#[test]
+fn sizes_must_match() {
+    let geometry = split_34().geometry().clone();
+    let config = small_config();
+    assert!(Checkpoint::new(&config, geometry.clone(), None).is_err());
+    assert!(Checkpoint::new(&config, geometry, Some(&Keyboard::new_47())).is_err());
+    assert!(Checkpoint::new(&config, Keyboard::new_47().geometry().clone(), Some(&Keyboard::new_47())).is_ok());
+}
+
+#[test]
+fn go_reports_bad_configs() {
+    let corpus = Corpus::from_text("");
+    assert!(go(&corpus, &GaConfig { survivors: 30, ..small_config() }, None).is_err());
+    let moves = GaConfig::from_toml("[moves]\npinned = { q = 99 }\n").unwrap().moves;
+    assert!(go(&corpus, &GaConfig { moves, ..small_config() }, None).is_err());
+}
+
+#[test]
+fn checkpoints_must_fit_their_config() {
+    let path = std::env::temp_dir().join(format!("keyboard_gen_fit_{}.json", std::process::id()));
+    let config = small_config();
+    let fresh = Checkpoint::new(&config, Keyboard::new_47().geometry().clone(), None).unwrap();
+    let mut started = fresh.clone();
+    started.groups[0].population = vec![Keyboard::new_47(); config.population];
+    started.groups[0].score_history = vec![0.; config.stagnation_window];
+
+    let load = |checkpoint: &Checkpoint| {
+        checkpoint.save(&path).unwrap();
+        Checkpoint::load(&path)
+    };
+    assert!(load(&fresh).is_ok());
+    assert!(load(&started).is_ok());
+
+    let mut changed = fresh.clone();
+    changed.groups.pop();
+    assert!(matches!(load(&changed), Err(CheckpointError::Invalid(_))));
+    let mut changed = started.clone();
+    changed.groups[0].population.pop();
+    assert!(matches!(load(&changed), Err(CheckpointError::Invalid(_))));
+    let mut changed = started.clone();
+    changed.groups[0].score_history.push(0.);
+    assert!(matches!(load(&changed), Err(CheckpointError::Invalid(_))));
+    let mut changed = started;
+    changed.config.survivors = 0;
+    assert!(matches!(load(&changed), Err(CheckpointError::Invalid(_))));
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,70 @@
+use keyboard_gen::{Corpus, Finger, Keyboard, Metric, SameFingerBigrams, Trigram};
+use Finger::*;
+
+#[test]
+fn trigram_kinds() {
+    let table = [
+        // alternate
+        ([LIndex, RIndex, LMid], Trigram::Alternate),
+        ([RPinky, LPinky, RPinky], Trigram::Alternate),
+        // two keys on one hand towards the index finger, then the other hand
+        ([LPinky, LRing, RMid], Trigram::InwardRoll),
+        ([RMid, LRing, LIndex], Trigram::InwardRoll),
+        ([RRing, RIndex, LMid], Trigram::InwardRoll),
+        // the same towards the pinky
+        ([LIndex, LMid, RIndex], Trigram::OutwardRoll),
+        ([LMid, RIndex, RPinky], Trigram::OutwardRoll),
+        // one hand, one direction
+        ([LPinky, LMid, LIndex], Trigram::OneHand),
+        ([RIndex, RRing, RPinky], Trigram::OneHand),
+        // one hand, changing direction
+        ([LPinky, LIndex, LMid], Trigram::Redirect),
+        ([RMid, RPinky, RIndex], Trigram::Redirect),
+        // a finger twice in a row, on one hand or not
+        ([LIndex, LIndex, LMid], Trigram::Other),
+        ([RMid, LMid, LMid], Trigram::Other),
+        ([LMid, RMid, RMid], Trigram::Other),
+        // thumbs sit past the index fingers, so moving onto them is inward
+        ([LIndex, LThumb, RMid], Trigram::InwardRoll),
+        ([RThumb, RIndex, LMid], Trigram::OutwardRoll),
+        ([LPinky, LIndex, LThumb], Trigram::OneHand),
+        ([LThumb, RThumb, LThumb], Trigram::Alternate),
+    ];
+    for ([f1, f2, f3], kind) in table {
+        assert_eq!(Trigram::from_fingers(f1, f2, f3), kind, "{:?} {:?} {:?}", f1, f2, f3);
+    }
+}
+
+#[test]
+fn load_percent() {
+    // `é` isn't on the keyboard, so three of four keystrokes are `a`s
+    let stats = Keyboard::new_47().stats(&Corpus::from_text("aaaeé"));
+    assert_eq!(stats.keystrokes, 4);
+    assert_eq!(stats.load_percent(LPinky), 75.);
+    assert_eq!(stats.load_percent(LIndex), 25.);
+    assert_eq!(stats.load_percent(RPinky), 0.);
+    assert_eq!(Keyboard::new_47().stats(&Corpus::from_text("")).load_percent(LPinky), 0.);
+}
+
+#[test]
+fn same_finger_bigrams() {
+    // on new_47 `e` and `c` are both under the left index finger, `a` and
+    // `t` under the left pinky and middle finger
+    let keyboard = Keyboard::new_47();
+    assert!(keyboard.is_sfb(29, 30));
+    assert!(keyboard.is_sfb(30, 29));
+    // the same key twice isn't
+    assert!(!keyboard.is_sfb(29, 29));
+    assert!(!keyboard.is_sfb(29, 28));
+    assert!(!keyboard.is_sfb(29, 99));
+    assert!(!keyboard.is_sfb(99, 99));
+
+    // `ec` and `ce` of the five bigrams on the keyboard; `cc` repeats a key
+    // and `té` leaves it
+    let corpus = Corpus::from_text("ecceaté");
+    let stats = keyboard.stats(&corpus);
+    assert_eq!((stats.sfb, stats.bigrams), (2, 5));
+    assert_eq!(stats.sfb_percent(), 40.);
+    assert_eq!(SameFingerBigrams.cost(&keyboard, &corpus), 2.);
+    assert_eq!(Keyboard::new_47().stats(&Corpus::from_text("")).sfb_percent(), 0.);
+}
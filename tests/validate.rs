@@ -0,0 +1,48 @@
+use keyboard_gen::{GaConfig, Key, Keyboard, KeyboardError};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+mod common;
+
+use common::with_moves;
+
+/// `new_47` with the key at `position` replaced.
+fn with_key(position: usize, key: Key) -> Result<(), KeyboardError> {
+    let keyboard = Keyboard::new_47();
+    let mut keys = keyboard.keys().to_vec();
+    keys[position] = key;
+    Keyboard::with_geometry(keyboard.geometry().clone(), keys).unwrap().validate()
+}
+
+#[test]
+fn built_in_layouts() {
+    assert_eq!(Keyboard::new_47().validate(), Ok(()));
+    for seed in 0..100 {
+        let keyboard = Keyboard::new_random(&mut ChaCha8Rng::seed_from_u64(seed));
+        assert_eq!(keyboard.validate(), Ok(()), "{}", keyboard);
+    }
+}
+
+#[test]
+fn duplicates_and_missing() {
+    // `x` sits at 41 in new_47
+    assert_eq!(with_key(41, Key::Letter('a', 'A')), Err(KeyboardError::Duplicate('a')));
+    assert_eq!(with_key(41, Key::Punctuation('9', '@')), Err(KeyboardError::Duplicate('9')));
+    assert_eq!(with_key(41, Key::Letter('ü', 'Ü')), Err(KeyboardError::Missing('x')));
+}
+
+#[test]
+fn shift_pairs() {
+    assert_eq!(with_key(41, Key::Letter('x', 'x')), Err(KeyboardError::BadShift { position: 41, base: 'x', shift: 'x' }));
+    assert_eq!(with_key(41, Key::Letter('x', 'Y')), Err(KeyboardError::BadShift { position: 41, base: 'x', shift: 'Y' }));
+    assert_eq!(with_key(37, Key::Punctuation(';', ';')), Err(KeyboardError::BadShift { position: 37, base: ';', shift: ';' }));
+    assert_eq!(with_key(37, Key::Punctuation(';', '€')), Ok(()));
+}
+
+#[test]
+fn pins() {
+    let config = "[moves]\npinned = { x = 41, z = 37 }\n";
+    let keyboard = with_moves(config);
+    assert_eq!(keyboard.validate(), Err(KeyboardError::PinMoved { key: 'z', position: 37 }));
+    assert_eq!(GaConfig::from_toml(config).unwrap().moves.pin(&keyboard).unwrap().validate(), Ok(()));
+}